@@ -0,0 +1,260 @@
+use crate::{
+    ray::Ray,
+    vec::{Point3D, Transform, Vec3D},
+};
+
+/// Axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point3D,
+    pub max: Point3D,
+}
+
+impl Aabb {
+    pub fn new(a: Point3D, b: Point3D) -> Self {
+        Self {
+            min: Point3D::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            max: Point3D::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        }
+    }
+
+    /// Smallest box holding every point, or `None` if there are none.
+    pub fn from_points<I: IntoIterator<Item = Point3D>>(points: I) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(Self::new(first, first), |aabb, p| {
+            aabb.union(&Self::new(p, p))
+        }))
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: Point3D::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Point3D::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    pub fn centroid(&self) -> Point3D {
+        0.5 * (self.min + self.max)
+    }
+
+    /// Box around the transformed corners of this one.
+    pub fn transform(&self, transform: &Transform) -> Self {
+        let corners = (0..8).map(|i| {
+            transform.point(Point3D::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            ))
+        });
+        Self::from_points(corners).unwrap()
+    }
+
+    // Slab test against a precomputed reciprocal direction
+    fn hit(&self, ray: &Ray, inv_direction: Vec3D, t_min: f64, t_max: f64) -> bool {
+        let (mut t_min, mut t_max) = (t_min, t_max);
+        for axis in 0..3 {
+            let mut t0 = (self.min[axis] - ray.origin[axis]) * inv_direction[axis];
+            let mut t1 = (self.max[axis] - ray.origin[axis]) * inv_direction[axis];
+            if inv_direction[axis] < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // Written so that NaNs from 0 * inf leave the interval unchanged
+            t_min = if t0 > t_min { t0 } else { t_min };
+            t_max = if t1 < t_max { t1 } else { t_max };
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+enum Node {
+    Leaf {
+        bounds: Aabb,
+        start: usize,
+        count: usize,
+    },
+    // The first child directly follows its parent
+    Interior {
+        bounds: Aabb,
+        second: usize,
+        axis: usize,
+    },
+}
+
+impl Node {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            Node::Leaf { bounds, .. } | Node::Interior { bounds, .. } => bounds,
+        }
+    }
+}
+
+const MAX_LEAF_SIZE: usize = 4;
+
+/// Bounding volume hierarchy over a list of boxes.
+///
+/// It only stores indices into the caller's list, so the same structure
+/// serves scenes of objects and meshes of triangles.
+pub struct Bvh {
+    nodes: Vec<Node>,
+    indices: Vec<usize>,
+}
+
+impl Bvh {
+    pub fn new(bounds: &[Aabb]) -> Self {
+        let mut bvh = Self {
+            nodes: Vec::with_capacity(2 * bounds.len()),
+            indices: (0..bounds.len()).collect(),
+        };
+        if !bounds.is_empty() {
+            bvh.build(bounds, 0, bounds.len());
+        }
+        bvh
+    }
+
+    // Splits at the median centroid along the axis where centroids spread most
+    fn build(&mut self, bounds: &[Aabb], start: usize, end: usize) {
+        let items = &mut self.indices[start..end];
+        let node_bounds = items
+            .iter()
+            .map(|&i| bounds[i])
+            .reduce(|a, b| a.union(&b))
+            .unwrap();
+
+        if items.len() <= MAX_LEAF_SIZE {
+            self.nodes.push(Node::Leaf {
+                bounds: node_bounds,
+                start,
+                count: items.len(),
+            });
+            return;
+        }
+
+        let centroids = Aabb::from_points(items.iter().map(|&i| bounds[i].centroid())).unwrap();
+        let extent = centroids.max - centroids.min;
+        let axis = (0..3)
+            .max_by(|&a, &b| extent[a].total_cmp(&extent[b]))
+            .unwrap();
+
+        let mid = items.len() / 2;
+        items.select_nth_unstable_by(mid, |&a, &b| {
+            bounds[a].centroid()[axis].total_cmp(&bounds[b].centroid()[axis])
+        });
+
+        let node = self.nodes.len();
+        self.nodes.push(Node::Interior {
+            bounds: node_bounds,
+            second: 0,
+            axis,
+        });
+        self.build(bounds, start, start + mid);
+        let second_child = self.nodes.len();
+        if let Node::Interior { second, .. } = &mut self.nodes[node] {
+            *second = second_child;
+        }
+        self.build(bounds, start + mid, end);
+    }
+
+    /// Finds the closest hit along `ray`. `hit` is called with the index of
+    /// each item whose box the ray reaches and the current closest distance,
+    /// and returns the item's hit distance with whatever it wants to keep.
+    pub fn hit<R, F>(&self, ray: &Ray, t_min: f64, t_max: f64, mut hit: F) -> Option<R>
+    where
+        F: FnMut(usize, f64) -> Option<(f64, R)>,
+    {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let inv_direction = Vec3D::new(
+            1.0 / ray.direction.x,
+            1.0 / ray.direction.y,
+            1.0 / ray.direction.z,
+        );
+        let mut closest = None;
+        let mut closest_so_far = t_max;
+        let mut stack = vec![0];
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.bounds().hit(ray, inv_direction, t_min, closest_so_far) {
+                continue;
+            }
+
+            match *node {
+                Node::Leaf { start, count, .. } => {
+                    for &item in &self.indices[start..start + count] {
+                        if let Some((t, result)) = hit(item, closest_so_far) {
+                            closest_so_far = t;
+                            closest = Some(result);
+                        }
+                    }
+                }
+                Node::Interior { second, axis, .. } => {
+                    // Visit the nearer child first so the far one can be culled
+                    if ray.direction[axis] < 0.0 {
+                        stack.push(index + 1);
+                        stack.push(second);
+                    } else {
+                        stack.push(second);
+                        stack.push(index + 1);
+                    }
+                }
+            }
+        }
+
+        closest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bvh_finds_closest() {
+        // Unit boxes along the x axis, standing in for objects
+        let bounds: Vec<Aabb> = (0..100)
+            .map(|i| {
+                let x = i as f64 * 2.0;
+                Aabb::new(Point3D::new(x, 0.0, 0.0), Point3D::new(x + 1.0, 1.0, 1.0))
+            })
+            .collect();
+        let bvh = Bvh::new(&bounds);
+
+        let ray = Ray::new(
+            Point3D::new(-10.0, 0.5, 0.5),
+            Vec3D::new(1.0, 0.0, 0.0),
+            0.0,
+        );
+        let mut tested = 0;
+        let hit = bvh.hit(&ray, 0.001, f64::INFINITY, |i, t_max| {
+            tested += 1;
+            let t = bounds[i].min.x - ray.origin.x;
+            (t < t_max).then_some((t, i))
+        });
+        assert_eq!(hit, Some(0));
+        assert!(tested < bounds.len());
+
+        let ray = Ray::new(
+            Point3D::new(-10.0, 5.0, 0.5),
+            Vec3D::new(1.0, 0.0, 0.0),
+            0.0,
+        );
+        assert_eq!(
+            bvh.hit(&ray, 0.001, f64::INFINITY, |i, _| Some((0.0, i))),
+            None
+        );
+    }
+}
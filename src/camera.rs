@@ -1,34 +1,111 @@
+pub mod aperture;
+pub mod lens;
+
+use std::f64::consts::PI;
+
+pub use aperture::{Aperture, ApertureMask};
+pub use lens::{LensElement, RealisticCamera};
+
 use crate::{
-    color::Color,
+    content::HashInto,
     ray::Ray,
-    vec::{Point3D, Vec3D},
+    sampler::Sampler,
+    vec::{Point3D, Transform, Vec3D},
 };
 
-pub struct Camera {
+/// Turns points on the image into rays into the scene, so rendering
+/// doesn't depend on the projection.
+pub trait Camera: Send + Sync {
+    /// Ray through `(u, v)`, which run from 0 to 1 across the image from
+    /// its bottom left corner, or `None` where the camera sees nothing.
+    /// Draws the lens (2D) and shutter time (1D) dimensions from `sampler`,
+    /// even when a camera has no use for them.
+    fn get_ray(&self, u: f64, v: f64, sampler: &mut dyn Sampler) -> Option<Ray>;
+
+    /// `get_ray` along with how much the ray's light counts, for cameras
+    /// that don't sample their lens evenly.
+    fn get_weighted_ray(&self, u: f64, v: f64, sampler: &mut dyn Sampler) -> Option<(Ray, f64)> {
+        self.get_ray(u, v, sampler).map(|ray| (ray, 1.0))
+    }
+
+    /// Factor radiance is scaled by on its way to the image.
+    fn exposure(&self) -> f64 {
+        1.0
+    }
+
+    /// Adds everything that decides the rays and exposure to `values`,
+    /// see `Hit::hash_into`.
+    fn hash_into(&self, values: &mut Vec<u64>);
+}
+
+// Time in `[open, close)` drawn from the sampler's next dimension
+fn shutter_time(open: f64, close: f64, sampler: &mut dyn Sampler) -> f64 {
+    open + sampler.get_1d() * (close - open)
+}
+
+/// How much of the scene a perspective camera takes in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldOfView {
+    /// Angle from the bottom to the top of the image, in degrees.
+    Vertical(f64),
+    /// Angle from the left to the right of the image, in degrees.
+    Horizontal(f64),
+    /// Focal length of the lens and height of the sensor behind it, in the
+    /// same units.
+    FocalLength {
+        focal_length: f64,
+        sensor_height: f64,
+    },
+}
+
+impl FieldOfView {
+    // Height of the image one unit in front of the lens
+    fn viewport_height(self, aspect_ratio: f64) -> f64 {
+        match self {
+            FieldOfView::Vertical(degrees) => 2.0 * (PI / 180.0 * degrees / 2.0).tan(),
+            FieldOfView::Horizontal(degrees) => {
+                2.0 * (PI / 180.0 * degrees / 2.0).tan() / aspect_ratio
+            }
+            FieldOfView::FocalLength {
+                focal_length,
+                sensor_height,
+            } => sensor_height / focal_length,
+        }
+    }
+}
+
+/// Pinhole or, with an aperture, thin lens camera.
+pub struct PerspectiveCamera {
     pub origin: Point3D,
     pub lower_left_corner: Point3D,
     pub horizontal: Vec3D,
     pub vertical: Vec3D,
-    pub focal_length: f64,
     pub cu: Vec3D,
     pub cv: Vec3D,
     pub lens_radius: f64,
+    /// Point on and normal of the plane in focus once tilted, see
+    /// `with_tilt`. Otherwise it is the plane of the image window.
+    pub focal_plane: Option<(Point3D, Vec3D)>,
+    pub aperture: Aperture,
+    /// Strength of the cat's-eye vignetting, see `with_vignetting`.
+    pub vignetting: f64,
+    pub exposure: f64,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
 }
 
-impl Camera {
+impl PerspectiveCamera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         lookfrom: Vec3D,
         lookat: Vec3D,
         view_up: Vec3D,
-        vertical_field_of_view: f64,
-        focal_length: f64,
+        field_of_view: FieldOfView,
         aspect_ratio: f64,
         aperture: f64,
         focus_distance: f64,
     ) -> Self {
-        // Vertical field-of-view in degrees
-        let theta = std::f64::consts::PI / 180.0 * vertical_field_of_view;
-        let viewport_height = 2.0 * (theta / 2.0).tan();
+        let viewport_height = field_of_view.viewport_height(aspect_ratio);
         let viewport_width = aspect_ratio * viewport_height;
 
         let cw = (lookfrom - lookat).normalize();
@@ -44,20 +121,963 @@ impl Camera {
             lower_left_corner,
             horizontal,
             vertical,
-            focal_length,
             cu,
             cv,
             lens_radius: aperture / 2.0,
+            focal_plane: None,
+            aperture: Aperture::Circle,
+            vignetting: 0.0,
+            exposure: 1.0,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+
+    /// Takes the field of view, lens and exposure from real camera
+    /// settings, with world units taken as meters.
+    pub fn physical(
+        lookfrom: Point3D,
+        lookat: Point3D,
+        view_up: Vec3D,
+        settings: &PhysicalSettings,
+        focus_distance: f64,
+    ) -> Self {
+        let mut camera = Self::new(
+            lookfrom,
+            lookat,
+            view_up,
+            FieldOfView::FocalLength {
+                focal_length: settings.focal_length,
+                sensor_height: settings.sensor_height,
+            },
+            settings.sensor_width / settings.sensor_height,
+            settings.aperture_diameter(),
+            focus_distance,
+        );
+        camera.exposure = settings.exposure();
+        camera
+    }
+
+    /// Spreads ray times over `[open, close)` so moving objects blur.
+    pub fn with_shutter(mut self, open: f64, close: f64) -> Self {
+        self.shutter_open = open;
+        self.shutter_close = close;
+        self
+    }
+
+    pub fn with_aperture(mut self, aperture: Aperture) -> Self {
+        self.aperture = aperture;
+        self
+    }
+
+    /// Clips the lens towards the edges of the image as a lens barrel
+    /// does, by a second disk that moves off the lens by `strength` lens
+    /// radii per half image height. Out-of-focus highlights narrow into
+    /// cat's eyes and the corners darken.
+    pub fn with_vignetting(mut self, strength: f64) -> Self {
+        self.vignetting = strength;
+        self
+    }
+
+    pub fn with_exposure(mut self, exposure: f64) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    /// Turns the camera counterclockwise about its view direction by
+    /// `degrees`, so the scene turns the other way in the image.
+    pub fn with_roll(mut self, degrees: f64) -> Self {
+        let roll = Transform::rotate(self.cu.cross(self.cv), degrees);
+        self.cu = roll.vector(self.cu);
+        self.cv = roll.vector(self.cv);
+        self.horizontal = roll.vector(self.horizontal);
+        self.vertical = roll.vector(self.vertical);
+        self.lower_left_corner = self.origin + roll.vector(self.lower_left_corner - self.origin);
+        self.focal_plane = self.focal_plane.map(|(point, normal)| {
+            (
+                self.origin + roll.vector(point - self.origin),
+                roll.vector(normal),
+            )
+        });
+        self
+    }
+
+    /// Slides the image across the image plane by `x` image widths and `y`
+    /// image heights, as a shift lens does. A level camera shifted up
+    /// takes in a tall building while keeping its walls parallel.
+    pub fn with_shift(mut self, x: f64, y: f64) -> Self {
+        self.lower_left_corner = self.lower_left_corner + x * self.horizontal + y * self.vertical;
+        self
+    }
+
+    /// Turns the plane in focus about the point in focus straight ahead,
+    /// as tilting the lens of a view camera does: by `tilt` degrees about
+    /// the horizontal so that the bottom of the image comes closer, and by
+    /// `swing` degrees about the vertical so that the right side does.
+    pub fn with_tilt(mut self, tilt: f64, swing: f64) -> Self {
+        let forward = self.cv.cross(self.cu);
+        let point = self.origin + (self.lower_left_corner - self.origin).dot(forward) * forward;
+        let normal =
+            -1.0 * forward + tilt.to_radians().tan() * self.cv - swing.to_radians().tan() * self.cu;
+        self.focal_plane = Some((point, normal.normalize()));
+        self
+    }
+}
+
+impl Camera for PerspectiveCamera {
+    fn get_ray(&self, u: f64, v: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        let (x, y) = self.aperture.sample(sampler.get_2d());
+        let time = shutter_time(self.shutter_open, self.shutter_close, sampler);
+        if self.vignetting > 0.0 {
+            let aspect_ratio = self.horizontal.length() / self.vertical.length();
+            let cx = self.vignetting * (2.0 * u - 1.0) * aspect_ratio;
+            let cy = self.vignetting * (2.0 * v - 1.0);
+            if (x - cx).hypot(y - cy) > 1.0 {
+                return None;
+            }
         }
+        let offset = self.lens_radius * (self.cu * x + self.cv * y);
+        let target = self.lower_left_corner + u * self.horizontal + v * self.vertical;
+
+        let Some((point, normal)) = self.focal_plane else {
+            return Some(Ray::new(
+                self.origin + offset,
+                target - self.origin - offset,
+                time,
+            ));
+        };
+        // Where the ray through the lens center meets the plane in focus,
+        // or at infinity if that is behind the camera
+        let direction = target - self.origin;
+        let t = normal.dot(point - self.origin) / normal.dot(direction);
+        let direction = if t > 0.0 && t.is_finite() {
+            t * direction - offset
+        } else {
+            direction
+        };
+        Some(Ray::new(self.origin + offset, direction, time))
     }
 
-    pub fn get_ray(&self, u: f64, v: f64) -> Ray {
-        let rd = self.lens_radius * Vec3D::random_in_unit_disk();
-        let offset = self.cu * rd.x + self.cv * rd.y;
+    fn exposure(&self) -> f64 {
+        self.exposure
+    }
 
-        Ray::new(
-            self.origin + offset,
-            self.lower_left_corner + u * self.horizontal + v * self.vertical - self.origin - offset,
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        (
+            "perspective",
+            self.origin,
+            self.lower_left_corner,
+            self.horizontal,
+            self.vertical,
+            self.cu,
+            self.cv,
+            self.lens_radius,
+        )
+            .hash_into(values);
+        (
+            self.focal_plane,
+            &self.aperture,
+            self.vignetting,
+            self.exposure,
+            self.shutter_open,
+            self.shutter_close,
         )
+            .hash_into(values);
+    }
+}
+
+/// Settings of a real camera and lens, in the units they are quoted in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhysicalSettings {
+    /// In millimeters.
+    pub focal_length: f64,
+    /// In millimeters.
+    pub sensor_width: f64,
+    pub sensor_height: f64,
+    pub f_number: f64,
+    /// Exposure time in seconds.
+    pub shutter_speed: f64,
+    pub iso: f64,
+}
+
+impl PhysicalSettings {
+    /// A full frame sensor at 1/125 s and ISO 100.
+    pub fn new(focal_length: f64, f_number: f64) -> Self {
+        Self {
+            focal_length,
+            sensor_width: 36.0,
+            sensor_height: 24.0,
+            f_number,
+            shutter_speed: 1.0 / 125.0,
+            iso: 100.0,
+        }
+    }
+
+    pub fn with_sensor(mut self, width: f64, height: f64) -> Self {
+        self.sensor_width = width;
+        self.sensor_height = height;
+        self
+    }
+
+    pub fn with_shutter_speed(mut self, seconds: f64) -> Self {
+        self.shutter_speed = seconds;
+        self
+    }
+
+    pub fn with_iso(mut self, iso: f64) -> Self {
+        self.iso = iso;
+        self
+    }
+
+    /// In degrees, with the lens focused at infinity.
+    pub fn vertical_field_of_view(&self) -> f64 {
+        2.0 * (self.sensor_height / (2.0 * self.focal_length))
+            .atan()
+            .to_degrees()
+    }
+
+    /// Diameter of the entrance pupil in meters.
+    pub fn aperture_diameter(&self) -> f64 {
+        self.focal_length / self.f_number / 1000.0
+    }
+
+    /// Scale from scene luminance in cd/m² to the image, with 1 reached
+    /// at the luminance that saturates the sensor (ISO 2720, with the
+    /// usual 1.2 headroom factor).
+    pub fn exposure(&self) -> f64 {
+        self.shutter_speed * self.iso / (120.0 * self.f_number * self.f_number)
+    }
+}
+
+/// Parallel rays over a rectangle of the view plane, so sizes don't shrink
+/// with distance. Only what lies in front of the plane through `lookfrom`
+/// is seen.
+pub struct OrthographicCamera {
+    pub lower_left_corner: Point3D,
+    pub horizontal: Vec3D,
+    pub vertical: Vec3D,
+    pub direction: Vec3D,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+}
+
+impl OrthographicCamera {
+    /// Sees `view_width` by `view_height` world units centered on
+    /// `lookfrom`.
+    pub fn new(
+        lookfrom: Point3D,
+        lookat: Point3D,
+        view_up: Vec3D,
+        view_width: f64,
+        view_height: f64,
+    ) -> Self {
+        let cw = (lookfrom - lookat).normalize();
+        let cu = view_up.cross(cw).normalize();
+        let cv = cw.cross(cu);
+
+        let horizontal = view_width * cu;
+        let vertical = view_height * cv;
+        Self {
+            lower_left_corner: lookfrom - horizontal / 2.0 - vertical / 2.0,
+            horizontal,
+            vertical,
+            direction: -1.0 * cw,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+
+    pub fn with_shutter(mut self, open: f64, close: f64) -> Self {
+        self.shutter_open = open;
+        self.shutter_close = close;
+        self
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn get_ray(&self, u: f64, v: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        // No lens, but its dimensions are still used up
+        sampler.get_2d();
+        let time = shutter_time(self.shutter_open, self.shutter_close, sampler);
+        Some(Ray::new(
+            self.lower_left_corner + u * self.horizontal + v * self.vertical,
+            self.direction,
+            time,
+        ))
+    }
+
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        (
+            "orthographic",
+            self.lower_left_corner,
+            self.horizontal,
+            self.vertical,
+            self.direction,
+            self.shutter_open,
+            self.shutter_close,
+        )
+            .hash_into(values);
+    }
+}
+
+// Right, up and forward directions of a camera at `lookfrom` facing
+// `lookat`
+fn frame(lookfrom: Point3D, lookat: Point3D, view_up: Vec3D) -> (Vec3D, Vec3D, Vec3D) {
+    let forward = (lookat - lookfrom).normalize();
+    let right = forward.cross(view_up).normalize();
+    (right, right.cross(forward), forward)
+}
+
+/// Full spherical panorama with longitude across the image and latitude up
+/// it, looking at `lookat` from the center.
+pub struct EquirectangularCamera {
+    pub origin: Point3D,
+    pub right: Vec3D,
+    pub up: Vec3D,
+    pub forward: Vec3D,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+}
+
+impl EquirectangularCamera {
+    pub fn new(lookfrom: Point3D, lookat: Point3D, view_up: Vec3D) -> Self {
+        let (right, up, forward) = frame(lookfrom, lookat, view_up);
+        Self {
+            origin: lookfrom,
+            right,
+            up,
+            forward,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+
+    pub fn with_shutter(mut self, open: f64, close: f64) -> Self {
+        self.shutter_open = open;
+        self.shutter_close = close;
+        self
+    }
+
+    /// Direction at a longitude and latitude in radians, both zero
+    /// straight ahead and increasing to the right and up.
+    pub fn direction(&self, longitude: f64, latitude: f64) -> Vec3D {
+        let horizontal = longitude.sin() * self.right + longitude.cos() * self.forward;
+        latitude.cos() * horizontal + latitude.sin() * self.up
+    }
+}
+
+impl Camera for EquirectangularCamera {
+    fn get_ray(&self, u: f64, v: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        sampler.get_2d();
+        let time = shutter_time(self.shutter_open, self.shutter_close, sampler);
+        let direction = self.direction((u - 0.5) * 2.0 * PI, (v - 0.5) * PI);
+        Some(Ray::new(self.origin, direction, time))
+    }
+
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        (
+            "equirectangular",
+            self.origin,
+            self.right,
+            self.up,
+            self.forward,
+            self.shutter_open,
+            self.shutter_close,
+        )
+            .hash_into(values);
+    }
+}
+
+/// How a fisheye lens spreads angles from its axis over the image circle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FisheyeMapping {
+    /// Distance from the center proportional to the angle.
+    Equidistant,
+    /// Equal solid angles cover equal areas of the image.
+    Equisolid,
+}
+
+/// Fisheye lens with a field of view of up to 360 degrees across an image
+/// circle that fills the shorter side of the image. Outside the circle
+/// nothing is seen.
+pub struct FisheyeCamera {
+    pub origin: Point3D,
+    pub right: Vec3D,
+    pub up: Vec3D,
+    pub forward: Vec3D,
+    /// Field of view across the image circle, in radians.
+    pub field_of_view: f64,
+    pub aspect_ratio: f64,
+    pub mapping: FisheyeMapping,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+}
+
+impl FisheyeCamera {
+    pub fn new(
+        lookfrom: Point3D,
+        lookat: Point3D,
+        view_up: Vec3D,
+        field_of_view: f64,
+        aspect_ratio: f64,
+        mapping: FisheyeMapping,
+    ) -> Self {
+        let (right, up, forward) = frame(lookfrom, lookat, view_up);
+        Self {
+            origin: lookfrom,
+            right,
+            up,
+            forward,
+            field_of_view: field_of_view.clamp(0.0, 360.0).to_radians(),
+            aspect_ratio,
+            mapping,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        }
+    }
+
+    pub fn with_shutter(mut self, open: f64, close: f64) -> Self {
+        self.shutter_open = open;
+        self.shutter_close = close;
+        self
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn get_ray(&self, u: f64, v: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        sampler.get_2d();
+        let time = shutter_time(self.shutter_open, self.shutter_close, sampler);
+
+        // Position in the image circle, of radius 1
+        let (x, y) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+        let (x, y) = if self.aspect_ratio >= 1.0 {
+            (x * self.aspect_ratio, y)
+        } else {
+            (x, y / self.aspect_ratio)
+        };
+        let r = x.hypot(y);
+        if r > 1.0 {
+            return None;
+        }
+
+        // Angle from the axis, reaching half the field of view at the rim
+        let half_fov = self.field_of_view / 2.0;
+        let theta = match self.mapping {
+            FisheyeMapping::Equidistant => r * half_fov,
+            FisheyeMapping::Equisolid => 2.0 * (r * (half_fov / 2.0).sin()).asin(),
+        };
+        let side = if r > 0.0 {
+            (x / r) * self.right + (y / r) * self.up
+        } else {
+            Vec3D::new(0.0, 0.0, 0.0)
+        };
+        let direction = theta.sin() * side + theta.cos() * self.forward;
+        Some(Ray::new(self.origin, direction, time))
+    }
+
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        let mapping = match self.mapping {
+            FisheyeMapping::Equidistant => "equidistant",
+            FisheyeMapping::Equisolid => "equisolid",
+        };
+        (
+            "fisheye",
+            self.origin,
+            self.right,
+            self.up,
+            self.forward,
+            self.field_of_view,
+            self.aspect_ratio,
+            mapping,
+        )
+            .hash_into(values);
+        (self.shutter_open, self.shutter_close).hash_into(values);
+    }
+}
+
+/// How the two eyes of a stereo pair share the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StereoLayout {
+    /// Left eye on the left half, right eye on the right.
+    SideBySide,
+    /// Left eye on the top half, right eye below.
+    TopBottom,
+}
+
+/// Two cameras rendered into one image, one per eye.
+pub struct StereoCamera {
+    pub left: Box<dyn Camera>,
+    pub right: Box<dyn Camera>,
+    pub layout: StereoLayout,
+}
+
+impl StereoCamera {
+    pub fn new(left: Box<dyn Camera>, right: Box<dyn Camera>, layout: StereoLayout) -> Self {
+        Self {
+            left,
+            right,
+            layout,
+        }
+    }
+
+    /// Perspective eyes `eye_separation` (the interpupillary distance)
+    /// apart looking the same way, their views shifted sideways so objects
+    /// at `convergence` distance line up in both. An infinite
+    /// `convergence` leaves the views unshifted. `aspect_ratio` is that of
+    /// one eye's half of the image.
+    #[allow(clippy::too_many_arguments)]
+    pub fn parallel(
+        lookfrom: Point3D,
+        lookat: Point3D,
+        view_up: Vec3D,
+        vertical_field_of_view: f64,
+        aspect_ratio: f64,
+        eye_separation: f64,
+        convergence: f64,
+        layout: StereoLayout,
+    ) -> Self {
+        let (right, _, forward) = frame(lookfrom, lookat, view_up);
+        let eye = |side: f64| {
+            let eye = lookfrom + side * eye_separation / 2.0 * right;
+            let mut camera = PerspectiveCamera::new(
+                eye,
+                eye + forward,
+                view_up,
+                FieldOfView::Vertical(vertical_field_of_view),
+                aspect_ratio,
+                0.0,
+                1.0,
+            );
+            // The view plane is one unit away
+            let shift = -side * eye_separation / 2.0 / convergence;
+            camera.lower_left_corner = camera.lower_left_corner + shift * camera.cu;
+            Box::new(camera)
+        };
+        Self::new(eye(-1.0), eye(1.0), layout)
+    }
+
+    /// Perspective eyes `eye_separation` apart, each turned in to look at
+    /// the point `convergence` ahead. Simpler than `parallel`, but the
+    /// turned views disagree vertically towards the corners.
+    #[allow(clippy::too_many_arguments)]
+    pub fn toe_in(
+        lookfrom: Point3D,
+        lookat: Point3D,
+        view_up: Vec3D,
+        vertical_field_of_view: f64,
+        aspect_ratio: f64,
+        eye_separation: f64,
+        convergence: f64,
+        layout: StereoLayout,
+    ) -> Self {
+        let (right, _, forward) = frame(lookfrom, lookat, view_up);
+        let eye = |side: f64| {
+            let eye = lookfrom + side * eye_separation / 2.0 * right;
+            let target = if convergence.is_finite() {
+                lookfrom + convergence * forward
+            } else {
+                eye + forward
+            };
+            Box::new(PerspectiveCamera::new(
+                eye,
+                target,
+                view_up,
+                FieldOfView::Vertical(vertical_field_of_view),
+                aspect_ratio,
+                0.0,
+                1.0,
+            ))
+        };
+        Self::new(eye(-1.0), eye(1.0), layout)
+    }
+
+    /// Omni-directional stereo: an equirectangular panorama per eye in
+    /// which every direction is seen from eyes `eye_separation` apart
+    /// across it, as if turning the head to face it. Rays converge at
+    /// `convergence`, and the eyes close in towards the poles where the
+    /// turning has no meaning.
+    pub fn omnidirectional(
+        lookfrom: Point3D,
+        lookat: Point3D,
+        view_up: Vec3D,
+        eye_separation: f64,
+        convergence: f64,
+        layout: StereoLayout,
+    ) -> Self {
+        let eye = |side: f64| {
+            Box::new(OmnidirectionalEye {
+                panorama: EquirectangularCamera::new(lookfrom, lookat, view_up),
+                offset: side * eye_separation / 2.0,
+                convergence,
+            })
+        };
+        Self::new(eye(-1.0), eye(1.0), layout)
+    }
+}
+
+impl StereoCamera {
+    // The eye that sees `(u, v)`, and where in its own image
+    fn eye(&self, u: f64, v: f64) -> (&dyn Camera, f64, f64) {
+        match self.layout {
+            StereoLayout::SideBySide if u < 0.5 => (self.left.as_ref(), 2.0 * u, v),
+            StereoLayout::SideBySide => (self.right.as_ref(), 2.0 * u - 1.0, v),
+            StereoLayout::TopBottom if v >= 0.5 => (self.left.as_ref(), u, 2.0 * v - 1.0),
+            StereoLayout::TopBottom => (self.right.as_ref(), u, 2.0 * v),
+        }
+    }
+}
+
+impl Camera for StereoCamera {
+    fn get_ray(&self, u: f64, v: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        let (eye, u, v) = self.eye(u, v);
+        eye.get_ray(u, v, sampler)
+    }
+
+    fn get_weighted_ray(&self, u: f64, v: f64, sampler: &mut dyn Sampler) -> Option<(Ray, f64)> {
+        let (eye, u, v) = self.eye(u, v);
+        eye.get_weighted_ray(u, v, sampler)
+    }
+
+    fn exposure(&self) -> f64 {
+        self.left.exposure()
+    }
+
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        let layout = match self.layout {
+            StereoLayout::SideBySide => "side by side",
+            StereoLayout::TopBottom => "top bottom",
+        };
+        ("stereo", layout).hash_into(values);
+        self.left.hash_into(values);
+        self.right.hash_into(values);
+    }
+}
+
+// One eye of an omni-directional stereo panorama, `offset` to the right of
+// the center (negative for the left eye)
+struct OmnidirectionalEye {
+    panorama: EquirectangularCamera,
+    offset: f64,
+    convergence: f64,
+}
+
+impl Camera for OmnidirectionalEye {
+    fn get_ray(&self, u: f64, v: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        let ray = self.panorama.get_ray(u, v, sampler)?;
+        let (longitude, latitude) = ((u - 0.5) * 2.0 * PI, (v - 0.5) * PI);
+        // To the right of the horizontal direction being looked in
+        let side = self.panorama.direction(longitude + PI / 2.0, 0.0);
+        let offset = self.offset * latitude.cos() * side;
+        let direction = if self.convergence.is_finite() {
+            self.convergence * ray.direction - offset
+        } else {
+            ray.direction
+        };
+        Some(Ray::new(ray.origin + offset, direction, ray.time))
+    }
+
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        ("omnidirectional eye", self.offset, self.convergence).hash_into(values);
+        self.panorama.hash_into(values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::IndependentSampler;
+
+    #[test]
+    fn test_orthographic_rays_are_parallel() {
+        let camera = OrthographicCamera::new(
+            Point3D::new(0.0, 0.0, 5.0),
+            Point3D::new(0.0, 0.0, 0.0),
+            Vec3D::new(0.0, 1.0, 0.0),
+            4.0,
+            2.0,
+        );
+        let sampler = &mut IndependentSampler::new(1, 0);
+        let corner = camera.get_ray(0.0, 0.0, sampler).unwrap();
+        let center = camera.get_ray(0.5, 0.5, sampler).unwrap();
+        let far = camera.get_ray(1.0, 1.0, sampler).unwrap();
+        assert_eq!(corner.origin, Point3D::new(-2.0, -1.0, 5.0));
+        assert_eq!(center.origin, Point3D::new(0.0, 0.0, 5.0));
+        assert_eq!(far.origin, Point3D::new(2.0, 1.0, 5.0));
+        for ray in [corner, center, far] {
+            assert_eq!(ray.direction, Vec3D::new(0.0, 0.0, -1.0));
+        }
+    }
+
+    fn assert_close(a: Vec3D, b: Vec3D) {
+        assert!((a - b).length() < 1.0e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_panoramas_cover_the_sphere() {
+        let (lookfrom, lookat) = (Point3D::new(1.0, 2.0, 3.0), Point3D::new(1.0, 2.0, 2.0));
+        let view_up = Vec3D::new(0.0, 1.0, 0.0);
+        let sampler = &mut IndependentSampler::new(1, 0);
+        let mut direction = |camera: &dyn Camera, u, v| {
+            let ray = camera.get_ray(u, v, sampler)?;
+            assert_eq!(ray.origin, lookfrom);
+            Some(ray.direction.normalize())
+        };
+
+        let camera = EquirectangularCamera::new(lookfrom, lookat, view_up);
+        assert_close(
+            direction(&camera, 0.5, 0.5).unwrap(),
+            Vec3D::new(0.0, 0.0, -1.0),
+        );
+        assert_close(
+            direction(&camera, 0.75, 0.5).unwrap(),
+            Vec3D::new(1.0, 0.0, 0.0),
+        );
+        assert_close(
+            direction(&camera, 0.0, 0.5).unwrap(),
+            Vec3D::new(0.0, 0.0, 1.0),
+        );
+        assert_close(
+            direction(&camera, 0.3, 1.0).unwrap(),
+            Vec3D::new(0.0, 1.0, 0.0),
+        );
+
+        for mapping in [FisheyeMapping::Equidistant, FisheyeMapping::Equisolid] {
+            let camera = FisheyeCamera::new(lookfrom, lookat, view_up, 180.0, 2.0, mapping);
+            assert_close(
+                direction(&camera, 0.5, 0.5).unwrap(),
+                Vec3D::new(0.0, 0.0, -1.0),
+            );
+            // The rim of a 180 degree lens looks sideways
+            assert_close(
+                direction(&camera, 0.5, 1.0).unwrap(),
+                Vec3D::new(0.0, 1.0, 0.0),
+            );
+            assert_close(
+                direction(&camera, 0.75, 0.5).unwrap(),
+                Vec3D::new(1.0, 0.0, 0.0),
+            );
+            assert!(direction(&camera, 0.9, 0.5).is_none());
+
+            let camera = FisheyeCamera::new(lookfrom, lookat, view_up, 360.0, 1.0, mapping);
+            assert_close(
+                direction(&camera, 1.0, 0.5).unwrap(),
+                Vec3D::new(0.0, 0.0, 1.0),
+            );
+        }
+
+        // Halfway out, equidistant is at half the angle while equisolid,
+        // which gives the center more of the image, is short of it
+        let equidistant = FisheyeCamera::new(
+            lookfrom,
+            lookat,
+            view_up,
+            180.0,
+            1.0,
+            FisheyeMapping::Equidistant,
+        );
+        let equisolid = FisheyeCamera::new(
+            lookfrom,
+            lookat,
+            view_up,
+            180.0,
+            1.0,
+            FisheyeMapping::Equisolid,
+        );
+        let angle = |d: Vec3D| d.dot(Vec3D::new(0.0, 0.0, -1.0)).acos().to_degrees();
+        assert!((angle(direction(&equidistant, 0.75, 0.5).unwrap()) - 45.0).abs() < 1.0e-9);
+        let expected = 2.0 * (0.5 * (PI / 4.0).sin()).asin();
+        assert!(
+            (angle(direction(&equisolid, 0.75, 0.5).unwrap()) - expected.to_degrees()).abs()
+                < 1.0e-9
+        );
+    }
+
+    // Point along `ray` closest to `target`, as a distance from it
+    fn miss(ray: &Ray, target: Point3D) -> f64 {
+        let direction = ray.direction.normalize();
+        let along = (target - ray.origin).dot(direction);
+        (ray.origin + along * direction - target).length()
+    }
+
+    #[test]
+    fn test_stereo_eyes_converge() {
+        let (lookfrom, lookat) = (Point3D::new(0.0, 1.0, 0.0), Point3D::new(0.0, 1.0, -1.0));
+        let view_up = Vec3D::new(0.0, 1.0, 0.0);
+        let converged = Point3D::new(0.0, 1.0, -3.0);
+        let sampler = &mut IndependentSampler::new(1, 0);
+        let rigs = [
+            StereoCamera::parallel(
+                lookfrom,
+                lookat,
+                view_up,
+                60.0,
+                1.0,
+                0.064,
+                3.0,
+                StereoLayout::SideBySide,
+            ),
+            StereoCamera::toe_in(
+                lookfrom,
+                lookat,
+                view_up,
+                60.0,
+                1.0,
+                0.064,
+                3.0,
+                StereoLayout::SideBySide,
+            ),
+            StereoCamera::omnidirectional(
+                lookfrom,
+                lookat,
+                view_up,
+                0.064,
+                3.0,
+                StereoLayout::SideBySide,
+            ),
+        ];
+        for rig in rigs {
+            // The middle of each half looks ahead from either side
+            let left = rig.get_ray(0.25, 0.5, sampler).unwrap();
+            let right = rig.get_ray(0.75, 0.5, sampler).unwrap();
+            assert_close(left.origin, Point3D::new(-0.032, 1.0, 0.0));
+            assert_close(right.origin, Point3D::new(0.032, 1.0, 0.0));
+            assert!(miss(&left, converged) < 1.0e-9);
+            assert!(miss(&right, converged) < 1.0e-9);
+        }
+
+        // Parallel eyes keep looking the same way
+        let rig = StereoCamera::parallel(
+            lookfrom,
+            lookat,
+            view_up,
+            60.0,
+            1.0,
+            0.064,
+            f64::INFINITY,
+            StereoLayout::TopBottom,
+        );
+        let left = rig.get_ray(0.5, 0.75, sampler).unwrap();
+        let right = rig.get_ray(0.5, 0.25, sampler).unwrap();
+        assert_close(left.origin, Point3D::new(-0.032, 1.0, 0.0));
+        assert_close(right.origin, Point3D::new(0.032, 1.0, 0.0));
+        assert_close(left.direction, right.direction);
+
+        // Looking right, the omni-directional eyes sit front and back
+        let rig = StereoCamera::omnidirectional(
+            lookfrom,
+            lookat,
+            view_up,
+            0.064,
+            f64::INFINITY,
+            StereoLayout::SideBySide,
+        );
+        let left = rig.get_ray(0.375, 0.5, sampler).unwrap();
+        assert_close(left.origin, Point3D::new(0.0, 1.0, -0.032));
+        assert_close(left.direction.normalize(), Vec3D::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_physical_settings() {
+        let settings = PhysicalSettings::new(50.0, 2.0).with_shutter_speed(1.0 / 100.0);
+        assert!((settings.vertical_field_of_view() - 26.9915).abs() < 1.0e-4);
+        assert!((settings.aperture_diameter() - 0.025).abs() < 1.0e-12);
+        // Two stops down takes four times the ISO to match
+        let darker = PhysicalSettings {
+            f_number: 4.0,
+            iso: 400.0,
+            ..settings
+        };
+        assert!((settings.exposure() - darker.exposure()).abs() < 1.0e-15);
+
+        let camera = PerspectiveCamera::physical(
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(0.0, 0.0, -1.0),
+            Vec3D::new(0.0, 1.0, 0.0),
+            &settings,
+            2.0,
+        );
+        assert_eq!(camera.lens_radius, 0.0125);
+        assert_eq!(camera.exposure(), settings.exposure());
+        assert!((camera.horizontal.length() / camera.vertical.length() - 1.5).abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn test_vignetting_clips_lens_off_center() {
+        let camera = PerspectiveCamera::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(0.0, 0.0, -1.0),
+            Vec3D::new(0.0, 1.0, 0.0),
+            FieldOfView::Vertical(60.0),
+            1.0,
+            0.5,
+            1.0,
+        )
+        .with_vignetting(0.6);
+        let sampler = &mut IndependentSampler::new(1, 0);
+        let mut seen = |u, v| {
+            (0..256)
+                .filter(|&i| {
+                    sampler.start_pixel_sample((i, 0), 0);
+                    camera.get_ray(u, v, sampler).is_some()
+                })
+                .count()
+        };
+        assert_eq!(seen(0.5, 0.5), 256);
+        let corner = seen(1.0, 1.0);
+        assert!(corner > 0 && corner < 192);
+    }
+
+    #[test]
+    fn test_roll_shift_and_tilt() {
+        let camera = |field_of_view, aperture| {
+            PerspectiveCamera::new(
+                Point3D::new(0.0, 0.0, 0.0),
+                Point3D::new(0.0, 0.0, -1.0),
+                Vec3D::new(0.0, 1.0, 0.0),
+                field_of_view,
+                1.0,
+                aperture,
+                2.0,
+            )
+        };
+        let wide = FieldOfView::FocalLength {
+            focal_length: 12.0,
+            sensor_height: 24.0,
+        };
+        assert_close(
+            camera(wide, 0.0).vertical,
+            camera(FieldOfView::Vertical(90.0), 0.0).vertical,
+        );
+
+        let sampler = &mut IndependentSampler::new(1, 0);
+        let mut ray = |camera: &PerspectiveCamera, u, v, i| {
+            sampler.start_pixel_sample((i, 0), 0);
+            camera.get_ray(u, v, sampler).unwrap()
+        };
+        let rolled = camera(wide, 0.0).with_roll(90.0);
+        assert_close(
+            ray(&rolled, 1.0, 0.5, 0).direction,
+            Vec3D::new(0.0, 2.0, -2.0),
+        );
+        let shifted = camera(wide, 0.0).with_shift(0.0, 0.25);
+        assert_close(
+            ray(&shifted, 0.5, 0.5, 0).direction,
+            Vec3D::new(0.0, 1.0, -2.0),
+        );
+
+        // Tilted down so the bottom edge focuses halfway in
+        let tilted = camera(wide, 0.5).with_tilt(45.0, 0.0);
+        for i in 0..8 {
+            let bottom = ray(&tilted, 0.5, 0.0, i);
+            assert_close(
+                bottom.origin + bottom.direction,
+                Point3D::new(0.0, -1.0, -1.0),
+            );
+            let center = ray(&tilted, 0.5, 0.5, i);
+            assert_close(
+                center.origin + center.direction,
+                Point3D::new(0.0, 0.0, -2.0),
+            );
+        }
     }
 }
@@ -0,0 +1,162 @@
+// Shapes of the lens opening. Out-of-focus highlights take the shape of the
+// aperture, so bladed and masked openings give bokeh their look.
+
+use std::{f64::consts::PI, sync::Arc};
+
+use crate::{content::HashInto, vec::Vec3D};
+
+/// Shape of the opening lens samples are taken from.
+#[derive(Clone, Default)]
+pub enum Aperture {
+    #[default]
+    Circle,
+    /// Regular polygon with a corner on the unit circle for every blade,
+    /// turned by `rotation` degrees.
+    Polygon {
+        blades: usize,
+        rotation: f64,
+    },
+    Mask(Arc<ApertureMask>),
+}
+
+impl Aperture {
+    /// Point on the opening for a pair of uniform numbers, in units of the
+    /// lens radius.
+    pub fn sample(&self, (u, v): (f64, f64)) -> (f64, f64) {
+        match self {
+            Aperture::Circle => {
+                let p = Vec3D::in_unit_disk((u, v));
+                (p.x, p.y)
+            }
+            Aperture::Polygon { blades, rotation } => {
+                // A triangle between the center and one edge, picked by `u`
+                let blades = (*blades).max(3);
+                let edge = ((u * blades as f64) as usize).min(blades - 1);
+                let u = u * blades as f64 - edge as f64;
+                let corner = |i: usize| {
+                    let angle = rotation.to_radians() + 2.0 * PI * i as f64 / blades as f64;
+                    (angle.cos(), angle.sin())
+                };
+                let ((x0, y0), (x1, y1)) = (corner(edge), corner(edge + 1));
+                let r = u.sqrt();
+                (r * ((1.0 - v) * x0 + v * x1), r * ((1.0 - v) * y0 + v * y1))
+            }
+            Aperture::Mask(mask) => mask.sample((u, v)),
+        }
+    }
+}
+
+/// Grayscale image of the opening over the square around the lens disk,
+/// rows from top to bottom. Brighter texels let through more light.
+pub struct ApertureMask {
+    width: usize,
+    height: usize,
+    // Running totals over the rows, and along each row
+    rows: Vec<f64>,
+    columns: Vec<f64>,
+}
+
+impl ApertureMask {
+    pub fn new(width: usize, height: usize, values: &[f64]) -> Self {
+        assert_eq!(
+            values.len(),
+            width * height,
+            "value count must match the size"
+        );
+        let mut columns = Vec::with_capacity(values.len());
+        let mut rows = Vec::with_capacity(height);
+        let mut total = 0.0;
+        for row in values.chunks(width.max(1)) {
+            let mut sum = 0.0;
+            for &value in row {
+                sum += value.max(0.0);
+                columns.push(sum);
+            }
+            total += sum;
+            rows.push(total);
+        }
+        assert!(total > 0.0, "mask must let some light through");
+        Self {
+            width,
+            height,
+            rows,
+            columns,
+        }
+    }
+
+    // Inverts the running totals, row by `v` and then column by `u`,
+    // keeping the remainders to place the point inside the texel
+    fn sample(&self, (u, v): (f64, f64)) -> (f64, f64) {
+        let (row, fy) = invert(&self.rows, v);
+        let columns = &self.columns[row * self.width..(row + 1) * self.width];
+        let (column, fx) = invert(columns, u);
+        let x = (column as f64 + fx) / self.width as f64;
+        let y = (row as f64 + fy) / self.height as f64;
+        (2.0 * x - 1.0, 1.0 - 2.0 * y)
+    }
+}
+
+impl HashInto for Aperture {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        match self {
+            Aperture::Circle => "circle".hash_into(values),
+            Aperture::Polygon { blades, rotation } => {
+                ("polygon", *blades, *rotation).hash_into(values)
+            }
+            Aperture::Mask(mask) => {
+                ("mask", mask.width, mask.height, &mask.rows, &mask.columns).hash_into(values)
+            }
+        }
+    }
+}
+
+// Index whose share of the running totals `sums` contains `t` of the
+// whole, and how far into that share it lies
+fn invert(sums: &[f64], t: f64) -> (usize, f64) {
+    let target = t * sums[sums.len() - 1];
+    let i = sums
+        .partition_point(|&sum| sum <= target)
+        .min(sums.len() - 1);
+    let start = if i > 0 { sums[i - 1] } else { 0.0 };
+    let fraction = ((target - start) / (sums[i] - start)).clamp(0.0, 1.0);
+    (i, fraction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apertures_stay_in_shape() {
+        let hexagon = Aperture::Polygon {
+            blades: 6,
+            rotation: 0.0,
+        };
+        // Right half of a 4 by 2 mask
+        let mask = Aperture::Mask(Arc::new(ApertureMask::new(
+            4,
+            2,
+            &[0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 1.0],
+        )));
+        let apothem = (PI / 6.0).cos();
+        let mut hexagon_reach: f64 = 0.0;
+        for i in 0..32 * 32 {
+            let sample = ((i % 32) as f64 / 32.0 + 0.01, (i / 32) as f64 / 32.0 + 0.01);
+
+            let (x, y) = Aperture::Circle.sample(sample);
+            assert!(x.hypot(y) <= 1.0);
+
+            let (x, y) = hexagon.sample(sample);
+            // Inside every edge
+            for edge in 0..6 {
+                let angle = PI / 6.0 + edge as f64 * PI / 3.0;
+                assert!(x * angle.cos() + y * angle.sin() <= apothem + 1.0e-12);
+            }
+            hexagon_reach = hexagon_reach.max(x.hypot(y));
+
+            let (x, y) = mask.sample(sample);
+            assert!((0.0..=1.0).contains(&x) && (-1.0..=1.0).contains(&y));
+        }
+        assert!(hexagon_reach > 0.95);
+    }
+}
@@ -0,0 +1,506 @@
+// Camera that traces rays through the spherical surfaces of a real lens,
+// so distortion, vignetting and the change in framing with focus come out
+// of the optics. Prescriptions list the surfaces from the front of the lens
+// to the back, one per line:
+//
+//     radius thickness ior aperture
+//
+// all in millimeters and with `#` starting a comment. A positive radius
+// bulges towards the scene and a radius of 0 marks the flat aperture stop.
+// The thickness is the distance to the next surface, the ior that of the
+// glass behind the surface (0 or 1 for air), and the aperture the diameter
+// of the opening. The last thickness, the distance to the sensor, is set
+// by focusing.
+
+use std::{fs, io, path::Path};
+
+use super::{frame, shutter_time, Camera};
+use crate::{
+    content::HashInto,
+    ray::Ray,
+    sampler::Sampler,
+    vec::{Point3D, Vec3D},
+};
+
+// Bands of distance from the center of the sensor with an exit pupil each,
+// and the grids searched for them on the sensor and the rear surface
+const PUPIL_BANDS: usize = 32;
+const PUPIL_FILM_SAMPLES: usize = 8;
+const PUPIL_LENS_SAMPLES: usize = 32;
+
+/// One spherical surface of a lens, in millimeters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LensElement {
+    /// Radius of curvature, positive when bulging towards the scene and 0
+    /// for the aperture stop.
+    pub radius: f64,
+    /// Distance along the axis to the next surface, or to the sensor.
+    pub thickness: f64,
+    /// Index of refraction behind the surface.
+    pub ior: f64,
+    /// Diameter of the opening.
+    pub aperture: f64,
+}
+
+impl HashInto for LensElement {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        (self.radius, self.thickness, self.ior, self.aperture).hash_into(values);
+    }
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Vec<LensElement>> {
+    parse(&fs::read_to_string(path)?)
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+pub fn parse(source: &str) -> io::Result<Vec<LensElement>> {
+    let mut elements = Vec::new();
+    for (number, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        if line.trim().is_empty() {
+            continue;
+        }
+        let values = line
+            .split_ascii_whitespace()
+            .map(|word| {
+                word.parse::<f64>()
+                    .ok()
+                    .filter(|value| value.is_finite())
+                    .ok_or_else(|| {
+                        invalid(format!("line {}: `{}` is not a number", number + 1, word))
+                    })
+            })
+            .collect::<io::Result<Vec<f64>>>()?;
+        let &[radius, thickness, ior, aperture] = &values[..] else {
+            return Err(invalid(format!(
+                "line {}: expected radius, thickness, ior and aperture",
+                number + 1
+            )));
+        };
+        if thickness < 0.0 || ior < 0.0 {
+            return Err(invalid(format!(
+                "line {}: thickness and ior can't be negative",
+                number + 1
+            )));
+        }
+        if aperture <= 0.0 {
+            return Err(invalid(format!(
+                "line {}: aperture must be positive",
+                number + 1
+            )));
+        }
+        elements.push(LensElement {
+            radius,
+            thickness,
+            ior: if ior == 0.0 { 1.0 } else { ior },
+            aperture,
+        });
+    }
+    if elements.is_empty() {
+        return Err(invalid("lens has no surfaces"));
+    }
+    Ok(elements)
+}
+
+// Bounds on the rear surface as (min x, min y, max x, max y)
+type Bounds = [f64; 4];
+
+fn area([x0, y0, x1, y1]: Bounds) -> f64 {
+    (x1 - x0) * (y1 - y0)
+}
+
+/// Camera with a lens made of spherical elements, in the spirit of pbrt's
+/// realistic camera. The lens is in millimeters and the scene in meters.
+pub struct RealisticCamera {
+    elements: Vec<LensElement>,
+    // Distance of each surface from the sensor along the axis
+    vertices: Vec<f64>,
+    sensor_width: f64,
+    sensor_height: f64,
+    // Where on the rear surface rays from each band of the sensor can get
+    // through the lens, found along +x and turned to the sensor point
+    pupils: Vec<Option<Bounds>>,
+    origin: Point3D,
+    right: Vec3D,
+    up: Vec3D,
+    forward: Vec3D,
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+}
+
+impl RealisticCamera {
+    /// Camera with its sensor at `lookfrom`, the lens in front of it moved
+    /// to focus at `focus_distance` meters from the sensor, which may be
+    /// infinite.
+    pub fn new(
+        lookfrom: Point3D,
+        lookat: Point3D,
+        view_up: Vec3D,
+        elements: Vec<LensElement>,
+        (sensor_width, sensor_height): (f64, f64),
+        focus_distance: f64,
+    ) -> Self {
+        assert!(!elements.is_empty(), "lens needs at least one surface");
+        let (right, up, forward) = frame(lookfrom, lookat, view_up);
+        let mut camera = Self {
+            vertices: Vec::new(),
+            elements,
+            sensor_width,
+            sensor_height,
+            pupils: Vec::new(),
+            origin: lookfrom,
+            right,
+            up,
+            forward,
+            shutter_open: 0.0,
+            shutter_close: 0.0,
+        };
+        camera.focus(1000.0 * focus_distance);
+
+        let radius = sensor_width.hypot(sensor_height) / 2.0;
+        camera.pupils = (0..PUPIL_BANDS)
+            .map(|band| {
+                let r0 = radius * band as f64 / PUPIL_BANDS as f64;
+                camera.exit_pupil(r0, r0 + radius / PUPIL_BANDS as f64)
+            })
+            .collect();
+        camera
+    }
+
+    /// Spreads ray times over `[open, close)` so moving objects blur.
+    pub fn with_shutter(mut self, open: f64, close: f64) -> Self {
+        self.shutter_open = open;
+        self.shutter_close = close;
+        self
+    }
+
+    /// The lens, with the last thickness as focused.
+    pub fn elements(&self) -> &[LensElement] {
+        &self.elements
+    }
+
+    fn set_back_focus(&mut self, distance: f64) {
+        if let Some(last) = self.elements.last_mut() {
+            last.thickness = distance;
+        }
+        let mut z = 0.0;
+        self.vertices = self
+            .elements
+            .iter()
+            .rev()
+            .map(|element| {
+                z += element.thickness;
+                z
+            })
+            .collect();
+        self.vertices.reverse();
+    }
+
+    // Moves the sensor to where a ray close to the axis from the point in
+    // focus crosses the axis behind the lens. The point stays put relative
+    // to the sensor, so this repeats until the two agree.
+    fn focus(&mut self, distance: f64) {
+        let back = self.elements[self.elements.len() - 1].thickness;
+        self.set_back_focus(back);
+        let height = 0.01 * self.elements[0].aperture / 2.0;
+        for _ in 0..32 {
+            let front = self.vertices[0];
+            let (origin, direction) = if distance.is_finite() {
+                let origin = Vec3D::new(0.0, 0.0, distance);
+                (
+                    origin,
+                    (Vec3D::new(height, 0.0, front) - origin).normalize(),
+                )
+            } else {
+                (
+                    Vec3D::new(height, 0.0, front + 1.0),
+                    Vec3D::new(0.0, 0.0, -1.0),
+                )
+            };
+            let Some((p, d)) = self.trace(origin, direction, false) else {
+                return;
+            };
+            // Rays that never meet the axis again focus nothing
+            let t = -p.x / d.x;
+            if t <= 0.0 || !t.is_finite() {
+                return;
+            }
+            let crossing = p.z + t * d.z;
+            let back = self.elements[self.elements.len() - 1].thickness - crossing;
+            if back <= 0.0 {
+                return;
+            }
+            self.set_back_focus(back);
+            if crossing.abs() < 1.0e-9 {
+                return;
+            }
+        }
+    }
+
+    // Bounds of the points on a square around the rear surface that rays
+    // from sensor points between `r0` and `r1` along +x get through the
+    // lens by. Grown by a grid cell so rays between the samples aren't lost.
+    fn exit_pupil(&self, r0: f64, r1: f64) -> Option<Bounds> {
+        let rear = self.vertices.len() - 1;
+        let extent = 1.5 * self.elements[rear].aperture / 2.0;
+        let cell = 2.0 * extent / PUPIL_LENS_SAMPLES as f64;
+        let mut bounds: Option<Bounds> = None;
+        for i in 0..PUPIL_FILM_SAMPLES {
+            let x = r0 + (i as f64 + 0.5) / PUPIL_FILM_SAMPLES as f64 * (r1 - r0);
+            let film = Vec3D::new(x, 0.0, 0.0);
+            for j in 0..PUPIL_LENS_SAMPLES * PUPIL_LENS_SAMPLES {
+                let px = -extent + ((j % PUPIL_LENS_SAMPLES) as f64 + 0.5) * cell;
+                let py = -extent + ((j / PUPIL_LENS_SAMPLES) as f64 + 0.5) * cell;
+                let direction = (Vec3D::new(px, py, self.vertices[rear]) - film).normalize();
+                if self.trace(film, direction, true).is_some() {
+                    bounds = Some(match bounds {
+                        Some([x0, y0, x1, y1]) => [x0.min(px), y0.min(py), x1.max(px), y1.max(py)],
+                        None => [px, py, px, py],
+                    });
+                }
+            }
+        }
+        bounds.map(|[x0, y0, x1, y1]| [x0 - cell, y0 - cell, x1 + cell, y1 + cell])
+    }
+
+    // Follows a ray in the lens frame, where the sensor is at z = 0 and the
+    // scene towards +z, out from the sensor or in from the scene. `None` if
+    // it misses a surface, an opening stops it or it reflects internally.
+    fn trace(&self, origin: Vec3D, direction: Vec3D, outward: bool) -> Option<(Point3D, Vec3D)> {
+        let (mut origin, mut direction) = (origin, direction);
+        let count = self.elements.len();
+        for k in 0..count {
+            let i = if outward { count - 1 - k } else { k };
+            let element = &self.elements[i];
+            let z = self.vertices[i];
+
+            let (t, normal) = if element.radius == 0.0 {
+                ((z - origin.z) / direction.z, None)
+            } else {
+                // Of the two crossings with the sphere, the one at the vertex
+                let center = Vec3D::new(0.0, 0.0, z - element.radius);
+                let oc = origin - center;
+                let b = oc.dot(direction);
+                let discriminant = b * b - oc.dot(oc) + element.radius * element.radius;
+                if discriminant < 0.0 {
+                    return None;
+                }
+                let closer = (direction.z > 0.0) == (element.radius < 0.0);
+                let t = if closer {
+                    -b - discriminant.sqrt()
+                } else {
+                    -b + discriminant.sqrt()
+                };
+                (t, Some(center))
+            };
+            if t.is_nan() || t <= 0.0 {
+                return None;
+            }
+            let p = origin + t * direction;
+            let radius = element.aperture / 2.0;
+            if p.x * p.x + p.y * p.y > radius * radius {
+                return None;
+            }
+            origin = p;
+
+            if let Some(center) = normal {
+                let mut normal = (p - center).normalize();
+                if normal.dot(direction) > 0.0 {
+                    normal = -1.0 * normal;
+                }
+                // The ior in front of a surface is that behind the one before
+                let front = if i > 0 { self.elements[i - 1].ior } else { 1.0 };
+                let eta = if outward {
+                    element.ior / front
+                } else {
+                    front / element.ior
+                };
+                direction = refract(direction, normal, eta)?;
+            }
+        }
+        Some((origin, direction))
+    }
+
+    // Ray in the lens frame through sensor point `film`, and its weight
+    fn sample_lens(&self, film: Vec3D, (u, v): (f64, f64)) -> Option<(Point3D, Vec3D, f64)> {
+        let r = film.x.hypot(film.y);
+        let radius = self.sensor_width.hypot(self.sensor_height) / 2.0;
+        let band = ((r / radius * PUPIL_BANDS as f64) as usize).min(PUPIL_BANDS - 1);
+        let bounds @ [x0, y0, x1, y1] = self.pupils[band]?;
+        let (px, py) = (x0 + u * (x1 - x0), y0 + v * (y1 - y0));
+        let (sin, cos) = if r > 0.0 {
+            (film.y / r, film.x / r)
+        } else {
+            (0.0, 1.0)
+        };
+        let rear = self.vertices[self.vertices.len() - 1];
+        let pupil = Vec3D::new(cos * px - sin * py, sin * px + cos * py, rear);
+        let direction = (pupil - film).normalize();
+        let (origin, direction_out) = self.trace(film, direction, true)?;
+
+        // Light falls off with the fourth power of the cosine at the sensor,
+        // and the pupil sampled over is relative to the one in the center
+        let cos4 = direction.z.powi(4);
+        let center = self.pupils[0].map_or(1.0, area);
+        Some((origin, direction_out, cos4 * area(bounds) / center))
+    }
+}
+
+// Snell's law for a unit direction and a normal facing it, or `None` past
+// the critical angle
+fn refract(direction: Vec3D, normal: Vec3D, eta: f64) -> Option<Vec3D> {
+    let cos_i = (-1.0 * direction).dot(normal);
+    let sin2_t = eta * eta * (1.0 - cos_i * cos_i);
+    if sin2_t > 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(eta * direction + (eta * cos_i - cos_t) * normal)
+}
+
+impl Camera for RealisticCamera {
+    fn get_ray(&self, u: f64, v: f64, sampler: &mut dyn Sampler) -> Option<Ray> {
+        self.get_weighted_ray(u, v, sampler).map(|(ray, _)| ray)
+    }
+
+    fn get_weighted_ray(&self, u: f64, v: f64, sampler: &mut dyn Sampler) -> Option<(Ray, f64)> {
+        let lens = sampler.get_2d();
+        let time = shutter_time(self.shutter_open, self.shutter_close, sampler);
+        // The lens turns the image around, so the sensor is read that way
+        let film = Vec3D::new(
+            (0.5 - u) * self.sensor_width,
+            (0.5 - v) * self.sensor_height,
+            0.0,
+        );
+        let (p, d, weight) = self.sample_lens(film, lens)?;
+        let to_world = |a: Vec3D| a.x * self.right + a.y * self.up + a.z * self.forward;
+        let ray = Ray::new(self.origin + to_world(p) / 1000.0, to_world(d), time);
+        Some((ray, weight))
+    }
+
+    // The exit pupils follow from the rest
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        (
+            "realistic",
+            &self.elements,
+            &self.vertices,
+            self.sensor_width,
+            self.sensor_height,
+        )
+            .hash_into(values);
+        (
+            self.origin,
+            self.right,
+            self.up,
+            self.forward,
+            self.shutter_open,
+            self.shutter_close,
+        )
+            .hash_into(values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::IndependentSampler;
+
+    // Double Gauss 50mm f/2 from US patent 2,673,491
+    const DOUBLE_GAUSS: &str = "
+        # radius  thickness  ior  aperture
+        29.475    3.76    1.67   25.2
+        84.83     0.12    1      25.2
+        19.275    4.025   1.67   23
+        40.77     3.275   1.699  23
+        12.75     5.705   1      18
+        0         4.5     0      17.1   # stop
+        -14.495   1.18    1.603  17
+        40.77     6.065   1.658  20
+        -20.385   0.19    1      20
+        437.065   3.22    1.717  20
+        -39.73    0       1      20
+    ";
+
+    #[test]
+    fn test_parse_prescription() {
+        let elements = parse(DOUBLE_GAUSS).unwrap();
+        assert_eq!(elements.len(), 11);
+        assert_eq!(elements[5].radius, 0.0);
+        assert_eq!(elements[5].ior, 1.0);
+        assert!(parse("10 1 1.5").is_err());
+        assert!(parse("10 1 1.5 x").is_err());
+        assert!(parse("# nothing\n").is_err());
+        assert!(parse("10 1 1.5 0").is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one surface")]
+    fn test_lens_needs_surfaces() {
+        RealisticCamera::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(0.0, 0.0, -1.0),
+            Vec3D::new(0.0, 1.0, 0.0),
+            Vec::new(),
+            (36.0, 24.0),
+            1.0,
+        );
+    }
+
+    #[test]
+    fn test_lens_focuses_and_breathes() {
+        let camera = |focus_distance| {
+            RealisticCamera::new(
+                Point3D::new(0.0, 0.0, 0.0),
+                Point3D::new(0.0, 0.0, -1.0),
+                Vec3D::new(0.0, 1.0, 0.0),
+                parse(DOUBLE_GAUSS).unwrap(),
+                (36.0, 24.0),
+                focus_distance,
+            )
+        };
+        let near = camera(1.0);
+        let far = camera(f64::INFINITY);
+        // A 50mm lens sits about 50mm from the sensor when focused far away
+        let back = far.elements()[10].thickness;
+        assert!(back > 30.0 && back < 60.0);
+        assert!(near.elements()[10].thickness > back);
+
+        let sampler = &mut IndependentSampler::new(1, 0);
+        let mut rays = |camera: &RealisticCamera, u, v| {
+            (0..64)
+                .filter_map(|i| {
+                    sampler.start_pixel_sample((i, 0), 0);
+                    camera.get_weighted_ray(u, v, sampler)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Rays from the center of the sensor meet again at the focus
+        let target = Point3D::new(0.0, 0.0, -1.0);
+        let center = rays(&near, 0.5, 0.5);
+        assert!(center.len() > 32);
+        for (ray, _) in &center {
+            let t = (target.z - ray.origin.z) / ray.direction.z;
+            assert!((ray.origin + t * ray.direction - target).length() < 2.0e-3);
+        }
+
+        // Light falls off towards the corners
+        let corner = rays(&near, 1.0, 1.0);
+        let total = |rays: &[(Ray, f64)]| rays.iter().map(|(_, w)| w).sum::<f64>();
+        assert!(total(&corner) < 0.8 * total(&center));
+
+        // Focusing closer narrows the view
+        let mut edge = |camera: &RealisticCamera| {
+            let rays = rays(camera, 1.0, 0.5);
+            let slopes = rays
+                .iter()
+                .map(|(ray, _)| ray.direction.x / -ray.direction.z);
+            slopes.sum::<f64>() / rays.len() as f64
+        };
+        let (near, far) = (edge(&near), edge(&far));
+        assert!(near > 0.0 && near < far);
+    }
+}
@@ -0,0 +1,142 @@
+// Saved state of a progressive render: the film's sums, each pixel's
+// sample statistics and hashes of what produced them. Stored as
+// little-endian integers after a magic number.
+
+use std::{fs, io, path::Path, time::Duration};
+
+const MAGIC: &[u8; 8] = b"RTCKPT02";
+const HEADER_SIZE: usize = 8 + 6 * 8;
+const PIXEL_SIZE: usize = 7 * 8;
+
+pub(crate) struct Checkpoint {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    /// Hash of the renderer settings that decide what samples add up to.
+    pub(crate) settings: u64,
+    pub(crate) scene: u64,
+    pub(crate) sampler: u64,
+    /// Rendering time spent before the checkpoint, over all runs.
+    pub(crate) elapsed: Duration,
+    pub(crate) film: Vec<[i64; 4]>,
+    /// Samples, mean and summed squared differences of each pixel.
+    pub(crate) stats: Vec<(u64, f64, f64)>,
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+impl Checkpoint {
+    /// Writes next to `path` first and then renames, so a render killed
+    /// mid-write still leaves the previous checkpoint intact.
+    pub(crate) fn save(&self, path: &Path) -> io::Result<()> {
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        fs::write(&temporary, self.to_bytes())?;
+        fs::rename(&temporary, path)
+    }
+
+    pub(crate) fn load(path: &Path) -> io::Result<Self> {
+        Self::parse(&fs::read(path)?)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + PIXEL_SIZE * self.film.len());
+        bytes.extend_from_slice(MAGIC);
+        for value in [
+            self.width as u64,
+            self.height as u64,
+            self.settings,
+            self.scene,
+            self.sampler,
+            self.elapsed.as_nanos() as u64,
+        ] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for (sums, &(samples, mean, m2)) in self.film.iter().zip(&self.stats) {
+            for sum in sums {
+                bytes.extend_from_slice(&sum.to_le_bytes());
+            }
+            bytes.extend_from_slice(&samples.to_le_bytes());
+            bytes.extend_from_slice(&mean.to_le_bytes());
+            bytes.extend_from_slice(&m2.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn parse(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.len() < HEADER_SIZE || &bytes[..8] != MAGIC {
+            return Err(invalid("not a render checkpoint"));
+        }
+        let word =
+            |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        let (width, height) = (word(8) as usize, word(16) as usize);
+        let pixels = width.checked_mul(height);
+        let size = pixels
+            .and_then(|pixels| pixels.checked_mul(PIXEL_SIZE))
+            .and_then(|size| size.checked_add(HEADER_SIZE));
+        let (Some(pixels), Some(size)) = (pixels, size) else {
+            return Err(invalid("checkpoint size overflows"));
+        };
+        if bytes.len() != size {
+            return Err(invalid(format!(
+                "checkpoint of {}x{} pixels should be {} bytes, not {}",
+                width,
+                height,
+                size,
+                bytes.len()
+            )));
+        }
+
+        let mut film = Vec::with_capacity(pixels);
+        let mut stats = Vec::with_capacity(pixels);
+        for i in 0..pixels {
+            let offset = HEADER_SIZE + PIXEL_SIZE * i;
+            film.push([0, 1, 2, 3].map(|j| word(offset + 8 * j) as i64));
+            stats.push((
+                word(offset + 32),
+                f64::from_bits(word(offset + 40)),
+                f64::from_bits(word(offset + 48)),
+            ));
+        }
+        Ok(Self {
+            width,
+            height,
+            settings: word(24),
+            scene: word(32),
+            sampler: word(40),
+            elapsed: Duration::from_nanos(word(48)),
+            film,
+            stats,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_round_trips() {
+        let checkpoint = Checkpoint {
+            width: 2,
+            height: 1,
+            settings: 1,
+            scene: 2,
+            sampler: 3,
+            elapsed: Duration::from_millis(1500),
+            film: vec![[1, -2, 3, 4], [5, 6, 7, 8]],
+            stats: vec![(4, 0.5, 0.25), (0, 0.0, 0.0)],
+        };
+        let bytes = checkpoint.to_bytes();
+        let parsed = Checkpoint::parse(&bytes).unwrap();
+        assert_eq!((parsed.width, parsed.height), (2, 1));
+        assert_eq!((parsed.settings, parsed.scene, parsed.sampler), (1, 2, 3));
+        assert_eq!(parsed.elapsed, checkpoint.elapsed);
+        assert_eq!(parsed.film, checkpoint.film);
+        assert_eq!(parsed.stats, checkpoint.stats);
+
+        assert!(Checkpoint::parse(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Checkpoint::parse(b"P3\n2 1\n255\n").is_err());
+    }
+}
@@ -1,7 +1,6 @@
 use std::fmt;
-use std::ops::Range;
 
-use crate::vec::Vec3D;
+use crate::{content::HashInto, vec::Vec3D};
 
 #[allow(dead_code)]
 #[derive(Clone, Copy)]
@@ -30,6 +29,12 @@ impl Color {
         }
     }
 
+    /// Perceived brightness (Rec. 709 weights).
+    pub fn luminance(&self) -> f64 {
+        let rgb = self.to_vec3d();
+        0.2126 * rgb.x + 0.7152 * rgb.y + 0.0722 * rgb.z
+    }
+
     /* pub fn random(range: Range<f64>) -> Self {
         Color::RGB(Vec3D::random(range))
     } */
@@ -49,3 +54,9 @@ impl fmt::Display for Color {
         }
     }
 }
+
+impl HashInto for Color {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        self.to_vec3d().hash_into(values);
+    }
+}
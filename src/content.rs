@@ -0,0 +1,109 @@
+// Exact contents of scenes, gathered as integers to be `hash`ed. Floats go
+// in as their bits, so any change at all gives a different hash.
+
+use std::sync::Arc;
+
+/// Value whose contents can go into a hash. Objects, materials, textures
+/// and cameras have a method of the same name on their own traits.
+pub trait HashInto {
+    fn hash_into(&self, values: &mut Vec<u64>);
+}
+
+impl HashInto for f64 {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        values.push(self.to_bits());
+    }
+}
+
+impl HashInto for u64 {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        values.push(*self);
+    }
+}
+
+impl HashInto for usize {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        values.push(*self as u64);
+    }
+}
+
+impl HashInto for bool {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        values.push(*self as u64);
+    }
+}
+
+/// Names tell apart types whose fields happen to hold the same numbers.
+impl HashInto for str {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        values.push(self.len() as u64);
+        values.extend(self.bytes().map(u64::from));
+    }
+}
+
+impl<T: HashInto + ?Sized> HashInto for &T {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        (**self).hash_into(values);
+    }
+}
+
+impl<T: HashInto + ?Sized> HashInto for Arc<T> {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        (**self).hash_into(values);
+    }
+}
+
+impl<T: HashInto> HashInto for [T] {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        values.push(self.len() as u64);
+        for item in self {
+            item.hash_into(values);
+        }
+    }
+}
+
+impl<T: HashInto> HashInto for Vec<T> {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        self.as_slice().hash_into(values);
+    }
+}
+
+impl<T: HashInto, const N: usize> HashInto for [T; N] {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        for item in self {
+            item.hash_into(values);
+        }
+    }
+}
+
+impl<T: HashInto> HashInto for Option<T> {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        match self {
+            Some(value) => {
+                values.push(1);
+                value.hash_into(values);
+            }
+            None => values.push(0),
+        }
+    }
+}
+
+macro_rules! hash_into_tuple {
+    ($($name:ident),+) => {
+        impl<$($name: HashInto),+> HashInto for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn hash_into(&self, values: &mut Vec<u64>) {
+                let ($($name,)+) = self;
+                $($name.hash_into(values);)+
+            }
+        }
+    };
+}
+
+hash_into_tuple!(A, B);
+hash_into_tuple!(A, B, C);
+hash_into_tuple!(A, B, C, D);
+hash_into_tuple!(A, B, C, D, E);
+hash_into_tuple!(A, B, C, D, E, F);
+hash_into_tuple!(A, B, C, D, E, F, G);
+hash_into_tuple!(A, B, C, D, E, F, G, H);
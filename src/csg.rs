@@ -0,0 +1,234 @@
+use std::sync::Arc;
+
+use crate::{
+    bvh::Aabb,
+    content::HashInto,
+    hit::{Hit, HitRecord, Span},
+    ray::Ray,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl CsgOperation {
+    fn contains(&self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+/// Boolean combination of two closed objects.
+///
+/// Both children are queried for the full list of spans the ray spends
+/// inside them, and the spans are merged according to the operation. Surfaces
+/// of the right child that bound a difference keep their own material but
+/// have their orientation flipped, so the cut faces inwards.
+#[derive(Clone)]
+pub struct Csg {
+    pub operation: CsgOperation,
+    pub left: Arc<dyn Hit>,
+    pub right: Arc<dyn Hit>,
+}
+
+impl Csg {
+    pub fn new(operation: CsgOperation, left: Arc<dyn Hit>, right: Arc<dyn Hit>) -> Self {
+        Self {
+            operation,
+            left,
+            right,
+        }
+    }
+
+    pub fn union(left: Arc<dyn Hit>, right: Arc<dyn Hit>) -> Self {
+        Self::new(CsgOperation::Union, left, right)
+    }
+
+    pub fn intersection(left: Arc<dyn Hit>, right: Arc<dyn Hit>) -> Self {
+        Self::new(CsgOperation::Intersection, left, right)
+    }
+
+    pub fn difference(left: Arc<dyn Hit>, right: Arc<dyn Hit>) -> Self {
+        Self::new(CsgOperation::Difference, left, right)
+    }
+}
+
+impl Hit for Csg {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.spans(ray)
+            .into_iter()
+            .flat_map(|span| [span.enter, span.exit])
+            .find(|hit_record| t_min <= hit_record.t && hit_record.t <= t_max)
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        match self.operation {
+            CsgOperation::Union => {
+                Some(self.left.bounding_box()?.union(&self.right.bounding_box()?))
+            }
+            // Either child bounds the overlap
+            CsgOperation::Intersection => self
+                .left
+                .bounding_box()
+                .or_else(|| self.right.bounding_box()),
+            CsgOperation::Difference => self.left.bounding_box(),
+        }
+    }
+
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        let left = self.left.spans(ray);
+        if left.is_empty() && self.operation != CsgOperation::Union {
+            return Vec::new();
+        }
+        let right = self.right.spans(ray);
+
+        // Sweep the boundaries of both children in order, tracking whether
+        // the ray is inside each one
+        let mut events: Vec<(HitRecord, bool)> = left
+            .into_iter()
+            .flat_map(|span| [(span.enter, true), (span.exit, true)])
+            .chain(
+                right
+                    .into_iter()
+                    .flat_map(|span| [(span.enter, false), (span.exit, false)]),
+            )
+            .collect();
+        events.sort_by(|(a, _), (b, _)| a.t.total_cmp(&b.t));
+
+        let mut spans = Vec::new();
+        let (mut in_left, mut in_right) = (false, false);
+        let mut enter = None;
+
+        for (mut hit_record, is_left) in events {
+            let was_inside = self.operation.contains(in_left, in_right);
+            if is_left {
+                in_left = hit_record.front_face;
+            } else {
+                in_right = hit_record.front_face;
+            }
+            let inside = self.operation.contains(in_left, in_right);
+
+            // The stored normal always faces the ray, so only the side flag
+            // needs to change when a boundary switches roles
+            if !was_inside && inside {
+                hit_record.front_face = true;
+                enter = Some(hit_record);
+            } else if was_inside && !inside {
+                if let Some(enter) = enter.take() {
+                    hit_record.front_face = false;
+                    spans.push(Span {
+                        enter,
+                        exit: hit_record,
+                    });
+                }
+            }
+        }
+
+        spans
+    }
+
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        let operation = match self.operation {
+            CsgOperation::Union => "union",
+            CsgOperation::Intersection => "intersection",
+            CsgOperation::Difference => "difference",
+        };
+        ("csg", operation).hash_into(values);
+        self.left.hash_into(values);
+        self.right.hash_into(values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        material::MaterialId,
+        object::{Cuboid, Cylinder, Sphere},
+        vec::{Point3D, Vec3D},
+    };
+
+    fn spans_t(object: &dyn Hit, ray: &Ray) -> Vec<(f64, f64)> {
+        object
+            .spans(ray)
+            .iter()
+            .map(|span| (span.enter.t, span.exit.t))
+            .collect()
+    }
+
+    fn unit_sphere(x: f64) -> Arc<dyn Hit> {
+        Arc::new(Sphere::new(Point3D::new(x, 0.0, 0.0), 1.0, MaterialId(0)))
+    }
+
+    fn approx(a: &[(f64, f64)], b: &[(f64, f64)]) -> bool {
+        a.len() == b.len()
+            && a.iter()
+                .zip(b)
+                .all(|(a, b)| (a.0 - b.0).abs() < 1.0e-9 && (a.1 - b.1).abs() < 1.0e-9)
+    }
+
+    #[test]
+    fn test_csg_operations() {
+        let ray = Ray::new(Point3D::new(-5.0, 0.0, 0.0), Vec3D::new(1.0, 0.0, 0.0), 0.0);
+        let (a, b) = (unit_sphere(0.0), unit_sphere(1.0));
+
+        let union = Csg::union(a.clone(), b.clone());
+        assert!(approx(&spans_t(&union, &ray), &[(4.0, 7.0)]));
+
+        let intersection = Csg::intersection(a.clone(), b.clone());
+        assert!(approx(&spans_t(&intersection, &ray), &[(5.0, 6.0)]));
+
+        let difference = Csg::difference(a, b);
+        assert!(approx(&spans_t(&difference, &ray), &[(4.0, 5.0)]));
+
+        // The cut surface of the difference is where the ray leaves the result
+        let hit = difference.hit(&ray, 4.5, f64::INFINITY).unwrap();
+        assert!((hit.t - 5.0).abs() < 1.0e-9);
+        assert!(!hit.front_face);
+        assert!((hit.normal - Vec3D::new(-1.0, 0.0, 0.0)).is_near_zero());
+    }
+
+    #[test]
+    fn test_csg_drilled_box() {
+        let material = MaterialId(0);
+        let block = Arc::new(Cuboid::new(
+            Point3D::new(-1.0, -1.0, -1.0),
+            Point3D::new(1.0, 1.0, 1.0),
+            material,
+        ));
+        let drill = Arc::new(
+            Cylinder::new(
+                Point3D::new(0.0, -2.0, 0.0),
+                Point3D::new(0.0, 2.0, 0.0),
+                0.5,
+                material,
+            )
+            .with_caps(),
+        );
+        let drilled = Csg::difference(block, drill);
+
+        // Down the hole nothing is hit
+        let down = Ray::new(Point3D::new(0.0, 5.0, 0.0), Vec3D::new(0.0, -1.0, 0.0), 0.0);
+        assert!(drilled.hit(&down, 0.001, f64::INFINITY).is_none());
+
+        // Across the block the ray passes through the hole between two walls
+        let across = Ray::new(Point3D::new(-5.0, 0.0, 0.0), Vec3D::new(1.0, 0.0, 0.0), 0.0);
+        assert!(approx(
+            &spans_t(&drilled, &across),
+            &[(4.0, 4.5), (5.5, 6.0)]
+        ));
+
+        // Nested operations see the combined spans
+        let capped = Csg::intersection(Arc::new(drilled), unit_sphere(0.0));
+        assert!(approx(
+            &spans_t(&capped, &across),
+            &[(4.0, 4.5), (5.5, 6.0)]
+        ));
+    }
+}
@@ -0,0 +1,406 @@
+use std::{
+    f64::consts::PI,
+    sync::atomic::{AtomicI64, Ordering},
+};
+
+use crate::{color::Color, tile::Tile, vec::Vec3D};
+
+/// Pixel reconstruction filter, weighting a sample by its offset from a
+/// pixel center in pixels. Each is separable and zero from `radius` out.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    Box {
+        radius: f64,
+    },
+    Tent {
+        radius: f64,
+    },
+    /// Gaussian shifted down to reach zero at `radius`.
+    Gaussian {
+        radius: f64,
+        sigma: f64,
+    },
+    /// Mitchell–Netravali cubic; `b` trades ringing for blur and `c`
+    /// sharpens.
+    Mitchell {
+        radius: f64,
+        b: f64,
+        c: f64,
+    },
+    /// Sinc windowed by a wider sinc, `tau` lobes across the window.
+    Lanczos {
+        radius: f64,
+        tau: f64,
+    },
+}
+
+impl Filter {
+    pub fn gaussian(radius: f64) -> Self {
+        Filter::Gaussian { radius, sigma: 0.5 }
+    }
+
+    /// With the parameters Mitchell and Netravali recommend.
+    pub fn mitchell(radius: f64) -> Self {
+        Filter::Mitchell {
+            radius,
+            b: 1.0 / 3.0,
+            c: 1.0 / 3.0,
+        }
+    }
+
+    pub fn lanczos(radius: f64) -> Self {
+        Filter::Lanczos { radius, tau: 3.0 }
+    }
+
+    pub fn radius(&self) -> f64 {
+        match *self {
+            Filter::Box { radius }
+            | Filter::Tent { radius }
+            | Filter::Gaussian { radius, .. }
+            | Filter::Mitchell { radius, .. }
+            | Filter::Lanczos { radius, .. } => radius,
+        }
+    }
+
+    pub fn evaluate(&self, x: f64, y: f64) -> f64 {
+        self.evaluate_1d(x) * self.evaluate_1d(y)
+    }
+
+    fn evaluate_1d(&self, x: f64) -> f64 {
+        let x = x.abs();
+        if x >= self.radius() {
+            return 0.0;
+        }
+        match *self {
+            Filter::Box { .. } => 1.0,
+            Filter::Tent { radius } => radius - x,
+            Filter::Gaussian { radius, sigma } => {
+                let gaussian = |x: f64| (-x * x / (2.0 * sigma * sigma)).exp();
+                gaussian(x) - gaussian(radius)
+            }
+            Filter::Mitchell { radius, b, c } => {
+                // The cubic spans [-2, 2]
+                let x = 2.0 * x / radius;
+                if x > 1.0 {
+                    ((-b - 6.0 * c) * x.powi(3)
+                        + (6.0 * b + 30.0 * c) * x.powi(2)
+                        + (-12.0 * b - 48.0 * c) * x
+                        + (8.0 * b + 24.0 * c))
+                        / 6.0
+                } else {
+                    ((12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+                        + (-18.0 + 12.0 * b + 6.0 * c) * x.powi(2)
+                        + (6.0 - 2.0 * b))
+                        / 6.0
+                }
+            }
+            Filter::Lanczos { tau, .. } => sinc(x) * sinc(x / tau),
+        }
+    }
+}
+
+impl Default for Filter {
+    /// One pixel wide box, averaging exactly the samples inside a pixel.
+    fn default() -> Self {
+        Filter::Box { radius: 0.5 }
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1.0e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+// Sums are kept in fixed point with this many steps per unit. Integer
+// addition doesn't depend on order, so neither does the image when threads
+// splat into the same pixels. 2^24 steps leave room for sums up to about
+// 5e11, enough for bright emitters over very long renders, and sums that
+// still get there saturate instead of wrapping around.
+const FIXED_POINT_SCALE: f64 = (1u64 << 24) as f64;
+
+fn to_fixed(value: f64) -> i64 {
+    (value * FIXED_POINT_SCALE).round() as i64
+}
+
+fn add_fixed(sum: &AtomicI64, value: f64) {
+    let value = to_fixed(value);
+    // Never fails, as the closure always returns a value
+    let _ = sum.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |sum| {
+        Some(sum.saturating_add(value))
+    });
+}
+
+#[derive(Debug, Default)]
+struct FilmPixel {
+    weighted_sum: [AtomicI64; 3],
+    weight: AtomicI64,
+}
+
+/// Image that every sample is splatted into, weighted by the filter, on
+/// each pixel whose center lies within the filter's radius. Samples can be
+/// added from many threads at once.
+pub struct Film {
+    width: usize,
+    height: usize,
+    filter: Filter,
+    pixels: Vec<FilmPixel>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize, filter: Filter) -> Self {
+        Self {
+            width,
+            height,
+            filter,
+            pixels: (0..width * height).map(|_| FilmPixel::default()).collect(),
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Adds a sample taken at `(x, y)` in pixels, from the bottom left
+    /// corner of the image.
+    pub fn add_sample(&self, position: (f64, f64), color: Vec3D) {
+        let bounds = (0, 0, self.width, self.height);
+        splat(&self.filter, position, bounds, |px, py, weight| {
+            self.add(px, py, weight * color, weight);
+        });
+    }
+
+    /// Buffer for the samples of one tile, which can be filled without
+    /// touching the shared film and added to it in one go.
+    pub fn tile(&self, tile: &Tile) -> FilmTile {
+        let margin = self.filter.radius().ceil() as usize;
+        let (x0, y0) = (
+            tile.x0.saturating_sub(margin),
+            tile.y0.saturating_sub(margin),
+        );
+        let x1 = (tile.x1 + margin).min(self.width);
+        let y1 = (tile.y1 + margin).min(self.height);
+        FilmTile {
+            bounds: (x0, y0, x1, y1),
+            filter: self.filter,
+            pixels: vec![(Vec3D::new(0.0, 0.0, 0.0), 0.0); (x1 - x0) * (y1 - y0)],
+        }
+    }
+
+    pub fn merge(&self, tile: FilmTile) {
+        let (x0, y0, x1, _) = tile.bounds;
+        for (i, (weighted_sum, weight)) in tile.pixels.into_iter().enumerate() {
+            if weight != 0.0 {
+                let (px, py) = (x0 + i % (x1 - x0), y0 + i / (x1 - x0));
+                self.add(px, py, weighted_sum, weight);
+            }
+        }
+    }
+
+    fn add(&self, x: usize, y: usize, weighted_sum: Vec3D, weight: f64) {
+        let pixel = &self.pixels[y * self.width + x];
+        let values = [weighted_sum.x, weighted_sum.y, weighted_sum.z];
+        for (sum, value) in pixel.weighted_sum.iter().zip(values) {
+            add_fixed(sum, value);
+        }
+        add_fixed(&pixel.weight, weight);
+    }
+
+    /// Every pixel's fixed point weighted sums and weight, row by row, to
+    /// be saved and later restored exactly with `set_sums`.
+    pub(crate) fn sums(&self) -> Vec<[i64; 4]> {
+        self.pixels
+            .iter()
+            .map(|pixel| {
+                let [r, g, b] = [0, 1, 2].map(|i| pixel.weighted_sum[i].load(Ordering::Relaxed));
+                [r, g, b, pixel.weight.load(Ordering::Relaxed)]
+            })
+            .collect()
+    }
+
+    pub(crate) fn set_sums(&self, sums: &[[i64; 4]]) {
+        for (pixel, sum) in self.pixels.iter().zip(sums) {
+            for (target, &value) in pixel.weighted_sum.iter().zip(&sum[..3]) {
+                target.store(value, Ordering::Relaxed);
+            }
+            pixel.weight.store(sum[3], Ordering::Relaxed);
+        }
+    }
+
+    /// Weighted average of the samples around a pixel, or black if none
+    /// carry weight.
+    pub fn color(&self, x: usize, y: usize) -> Color {
+        let pixel = &self.pixels[y * self.width + x];
+        let weight = pixel.weight.load(Ordering::Relaxed);
+        if weight <= 0 {
+            return Color::Black;
+        }
+        let [r, g, b] = [0, 1, 2].map(|i| pixel.weighted_sum[i].load(Ordering::Relaxed));
+        Color::RGB(Vec3D::new(r as f64, g as f64, b as f64) / weight as f64)
+    }
+
+    /// The image as a plain PPM, top row first.
+    pub fn to_ppm(&self) -> String {
+        let mut ppm = format!("P3\n{} {}\n255\n", self.width, self.height);
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                let color = self.color(x, y).to_vec3d().format_color(1);
+                ppm += &format!("{}\n", Color::RGB(color));
+            }
+        }
+        ppm
+    }
+}
+
+/// Samples splatted around one tile, see `Film::tile`.
+pub struct FilmTile {
+    // Pixels covered, from (x0, y0) up to (x1, y1)
+    bounds: (usize, usize, usize, usize),
+    filter: Filter,
+    pixels: Vec<(Vec3D, f64)>,
+}
+
+impl FilmTile {
+    pub fn add_sample(&mut self, position: (f64, f64), color: Vec3D) {
+        let (x0, y0, x1, _) = self.bounds;
+        let pixels = &mut self.pixels;
+        splat(&self.filter, position, self.bounds, |px, py, weight| {
+            let (weighted_sum, total) = &mut pixels[(py - y0) * (x1 - x0) + px - x0];
+            *weighted_sum = *weighted_sum + weight * color;
+            *total += weight;
+        });
+    }
+}
+
+// Calls `add` with each pixel inside `bounds` whose center is within the
+// filter's reach of `(x, y)`, and the sample's weight there
+fn splat<F>(filter: &Filter, (x, y): (f64, f64), bounds: (usize, usize, usize, usize), mut add: F)
+where
+    F: FnMut(usize, usize, f64),
+{
+    let (x0, y0, x1, y1) = bounds;
+    let radius = filter.radius();
+    let range = |center: f64, start: usize, end: usize| {
+        let first = (center - 0.5 - radius).ceil().max(start as f64) as usize;
+        let last = (center - 0.5 + radius).floor().min(end as f64 - 1.0);
+        first..(last + 1.0).max(first as f64) as usize
+    };
+    for py in range(y, y0, y1) {
+        for px in range(x, x0, x1) {
+            let weight = filter.evaluate(px as f64 + 0.5 - x, py as f64 + 0.5 - y);
+            if weight != 0.0 {
+                add(px, py, weight);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTERS: [Filter; 5] = [
+        Filter::Box { radius: 0.5 },
+        Filter::Tent { radius: 1.0 },
+        Filter::Gaussian {
+            radius: 1.5,
+            sigma: 0.5,
+        },
+        Filter::Mitchell {
+            radius: 2.0,
+            b: 1.0 / 3.0,
+            c: 1.0 / 3.0,
+        },
+        Filter::Lanczos {
+            radius: 3.0,
+            tau: 3.0,
+        },
+    ];
+
+    #[test]
+    fn test_filters_peak_at_center() {
+        for filter in FILTERS {
+            let center = filter.evaluate(0.0, 0.0);
+            assert!(center > 0.0);
+            assert!(filter.evaluate(0.25, 0.0) <= center);
+            assert_eq!(filter.evaluate(filter.radius(), 0.0), 0.0);
+            assert_eq!(filter.evaluate(0.0, -filter.radius()), 0.0);
+        }
+        // Mitchell and Lanczos dip below zero to sharpen
+        assert!(Filter::mitchell(2.0).evaluate(1.5, 0.0) < 0.0);
+        assert!(Filter::lanczos(3.0).evaluate(1.5, 0.0) < 0.0);
+    }
+
+    #[test]
+    fn test_film_keeps_flat_color() {
+        let color = Vec3D::new(0.2, 0.4, 0.8);
+        for filter in FILTERS {
+            let film = Film::new(4, 3, filter);
+            for i in 0..16 * 12 {
+                let (x, y) = ((i % 16) as f64 + 0.5, (i / 16) as f64 + 0.5);
+                film.add_sample((x / 4.0, y / 4.0), color);
+            }
+            for y in 0..3 {
+                for x in 0..4 {
+                    let error = film.color(x, y).to_vec3d() - color;
+                    assert!(error.length() < 1.0e-6, "{:?}", filter);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_box_keeps_samples_in_their_pixel() {
+        let film = Film::new(2, 1, Filter::default());
+        film.add_sample((0.3, 0.5), Vec3D::new(1.0, 1.0, 1.0));
+        film.add_sample((1.7, 0.5), Vec3D::new(0.0, 0.0, 0.0));
+        assert_eq!(film.color(0, 0).to_vec3d(), Vec3D::new(1.0, 1.0, 1.0));
+        assert_eq!(film.color(1, 0).to_vec3d(), Vec3D::new(0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_bright_samples_do_not_wrap() {
+        let film = Film::new(1, 1, Filter::default());
+        for _ in 0..100_000 {
+            film.add_sample((0.5, 0.5), Vec3D::new(5000.0, 1.0, 0.0));
+        }
+        let color = film.color(0, 0).to_vec3d();
+        assert!((color - Vec3D::new(5000.0, 1.0, 0.0)).length() < 1.0e-6);
+
+        // Past what the sums can hold they stay at their largest
+        for _ in 0..10 {
+            film.add_sample((0.5, 0.5), Vec3D::new(1.0e12, 1.0, 0.0));
+        }
+        assert!(film.color(0, 0).to_vec3d().x > 1.0e6);
+    }
+
+    #[test]
+    fn test_tiles_match_direct_splats() {
+        let (direct, tiled) = (
+            Film::new(6, 5, Filter::mitchell(2.0)),
+            Film::new(6, 5, Filter::mitchell(2.0)),
+        );
+        for tile in crate::tile::tiles(6, 5, 3, Default::default()) {
+            let mut buffer = tiled.tile(&tile);
+            for (x, y) in tile.pixels() {
+                let position = (x as f64 + 0.25, y as f64 + 0.75);
+                let color = Vec3D::new(x as f64, y as f64, 1.0);
+                direct.add_sample(position, color);
+                buffer.add_sample(position, color);
+            }
+            tiled.merge(buffer);
+        }
+        for y in 0..5 {
+            for x in 0..6 {
+                let error = direct.color(x, y).to_vec3d() - tiled.color(x, y).to_vec3d();
+                assert!(error.length() < 1.0e-6);
+            }
+        }
+    }
+}
@@ -0,0 +1,367 @@
+// glTF 2.0 scenes (`.gltf` with external or embedded buffers, and `.glb`).
+//
+// Every mesh primitive becomes one shared `TriangleMesh`, placed by an
+// `Instance` for each node that references it, so repeated meshes are only
+// stored once. Metallic-roughness materials map onto `PbrMaterial` and the
+// first camera found in the node hierarchy becomes the camera.
+
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use ::gltf::{
+    buffer, camera::Projection, image, mesh::Mode, scene::Node, texture, Document, Primitive,
+};
+
+use crate::{
+    camera::{FieldOfView, OrthographicCamera, PerspectiveCamera},
+    color::Color,
+    hit::Hit,
+    material::{MaterialId, PbrMaterial},
+    mesh::{MeshData, TriangleMesh},
+    object::Instance,
+    scene::SceneBuilder,
+    texture::{srgb_to_linear, ImageTexture, Texture},
+    vec::{Matrix4, Point3D, Transform, Vec3D},
+};
+
+/// Loads a `.gltf` or `.glb` file into a scene ready to `build`.
+/// `aspect_ratio` is used for cameras that leave it unspecified.
+pub fn load<P: AsRef<Path>>(path: P, aspect_ratio: f64) -> ::gltf::Result<SceneBuilder> {
+    let (document, buffers, images) = ::gltf::import(path)?;
+    Ok(Importer::new(&buffers, &images).import(&document, aspect_ratio))
+}
+
+/// Same as `load` for a file already in memory; external URIs are not resolved.
+pub fn parse(bytes: &[u8], aspect_ratio: f64) -> ::gltf::Result<SceneBuilder> {
+    let (document, buffers, images) = ::gltf::import_slice(bytes)?;
+    Ok(Importer::new(&buffers, &images).import(&document, aspect_ratio))
+}
+
+// glTF matrices are column-major
+fn to_matrix(columns: [[f32; 4]; 4]) -> Matrix4 {
+    let mut m = [[0.0; 4]; 4];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = columns[j][i] as f64;
+        }
+    }
+    Matrix4::new(m)
+}
+
+struct Importer<'a> {
+    buffers: &'a [buffer::Data],
+    images: &'a [image::Data],
+    textures: HashMap<(usize, bool), Option<Arc<dyn Texture>>>,
+    materials: HashMap<Option<usize>, MaterialId>,
+    primitives: HashMap<(usize, usize), Option<Arc<dyn Hit>>>,
+    scene: SceneBuilder,
+}
+
+impl<'a> Importer<'a> {
+    fn new(buffers: &'a [buffer::Data], images: &'a [image::Data]) -> Self {
+        Self {
+            buffers,
+            images,
+            textures: HashMap::new(),
+            materials: HashMap::new(),
+            primitives: HashMap::new(),
+            scene: SceneBuilder::new(),
+        }
+    }
+
+    fn import(mut self, document: &Document, aspect_ratio: f64) -> SceneBuilder {
+        if let Some(scene) = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+        {
+            for node in scene.nodes() {
+                self.visit(&node, Matrix4::identity(), aspect_ratio);
+            }
+        }
+
+        self.scene
+    }
+
+    fn visit(&mut self, node: &Node, parent: Matrix4, aspect_ratio: f64) {
+        let matrix = parent * to_matrix(node.transform().matrix());
+
+        if let (Some(mesh), Some(transform)) = (node.mesh(), Transform::new(matrix)) {
+            for primitive in mesh.primitives() {
+                if let Some(object) = self.primitive(mesh.index(), &primitive) {
+                    self.scene.add(Instance::new(object, transform));
+                }
+            }
+        }
+
+        if self.scene.camera().is_none() {
+            if let Some(camera) = node.camera() {
+                // Cameras look down -z with +y up in their local frame
+                let eye = matrix.transform_point(Point3D::new(0.0, 0.0, 0.0));
+                let forward = matrix.transform_vector(Vec3D::new(0.0, 0.0, -1.0));
+                let up = matrix.transform_vector(Vec3D::new(0.0, 1.0, 0.0));
+                match camera.projection() {
+                    Projection::Perspective(perspective) => {
+                        self.scene.set_camera(PerspectiveCamera::new(
+                            eye,
+                            eye + forward.normalize(),
+                            up,
+                            FieldOfView::Vertical((perspective.yfov() as f64).to_degrees()),
+                            perspective
+                                .aspect_ratio()
+                                .map_or(aspect_ratio, |aspect_ratio| aspect_ratio as f64),
+                            0.0,
+                            1.0,
+                        ));
+                    }
+                    // Magnifications are half the view's width and height
+                    Projection::Orthographic(orthographic) => {
+                        self.scene.set_camera(OrthographicCamera::new(
+                            eye,
+                            eye + forward.normalize(),
+                            up,
+                            2.0 * orthographic.xmag() as f64,
+                            2.0 * orthographic.ymag() as f64,
+                        ));
+                    }
+                }
+            }
+        }
+
+        for child in node.children() {
+            self.visit(&child, matrix, aspect_ratio);
+        }
+    }
+
+    fn primitive(&mut self, mesh: usize, primitive: &Primitive) -> Option<Arc<dyn Hit>> {
+        let key = (mesh, primitive.index());
+        if let Some(object) = self.primitives.get(&key) {
+            return object.clone();
+        }
+
+        let object = self.read_primitive(primitive);
+        self.primitives.insert(key, object.clone());
+        object
+    }
+
+    fn read_primitive(&mut self, primitive: &Primitive) -> Option<Arc<dyn Hit>> {
+        // Points and lines have no surface to hit
+        if primitive.mode() != Mode::Triangles {
+            return None;
+        }
+
+        let buffers = self.buffers;
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data[..]));
+        let f64s = |[x, y, z]: [f32; 3]| Vec3D::new(x as f64, y as f64, z as f64);
+
+        let positions: Vec<Point3D> = reader.read_positions()?.map(f64s).collect();
+        let normals = reader
+            .read_normals()
+            .map(|normals| normals.map(f64s).collect())
+            .unwrap_or_default();
+        // glTF puts v = 0 at the top of the image
+        let uvs = reader
+            .read_tex_coords(0)
+            .map(|uvs| {
+                uvs.into_f32()
+                    .map(|[u, v]| (u as f64, 1.0 - v as f64))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let flat: Vec<usize> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().map(|index| index as usize).collect(),
+            None => (0..positions.len()).collect(),
+        };
+        let indices = flat
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .filter(|triangle| triangle.iter().all(|&index| index < positions.len()))
+            .collect();
+
+        let data = MeshData {
+            positions,
+            normals,
+            uvs,
+            indices,
+            ..MeshData::default()
+        };
+        let material = self.material(&primitive.material());
+
+        Some(Arc::new(TriangleMesh::new(Arc::new(data), material)))
+    }
+
+    fn material(&mut self, material: &::gltf::Material) -> MaterialId {
+        if let Some(&id) = self.materials.get(&material.index()) {
+            return id;
+        }
+
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _] = pbr.base_color_factor();
+        let mut result = PbrMaterial::new(
+            Color::new(r as f64, g as f64, b as f64),
+            pbr.metallic_factor() as f64,
+            pbr.roughness_factor() as f64,
+        );
+
+        if let Some(texture) = pbr
+            .base_color_texture()
+            .and_then(|info| self.texture(&info.texture(), true))
+        {
+            result = result.with_base_color_texture(texture);
+        }
+        if let Some(texture) = pbr
+            .metallic_roughness_texture()
+            .and_then(|info| self.texture(&info.texture(), false))
+        {
+            result = result.with_metallic_roughness_texture(texture);
+        }
+        if let Some(normal) = material.normal_texture() {
+            if let Some(texture) = self.texture(&normal.texture(), false) {
+                result = result.with_normal_texture(texture, normal.scale() as f64);
+            }
+        }
+
+        let [r, g, b] = material.emissive_factor();
+        result = result.with_emissive(Color::new(r as f64, g as f64, b as f64));
+        if let Some(texture) = material
+            .emissive_texture()
+            .and_then(|info| self.texture(&info.texture(), true))
+        {
+            result = result.with_emissive_texture(texture);
+        }
+
+        let id = self.scene.add_material(Arc::new(result));
+        self.materials.insert(material.index(), id);
+        id
+    }
+
+    fn texture(&mut self, texture: &texture::Texture, srgb: bool) -> Option<Arc<dyn Texture>> {
+        let key = (texture.source().index(), srgb);
+        if let Some(texture) = self.textures.get(&key) {
+            return texture.clone();
+        }
+
+        let result = self
+            .images
+            .get(key.0)
+            .map(|image| Arc::new(decode_image(image, srgb)) as Arc<dyn Texture>);
+        self.textures.insert(key, result.clone());
+        result
+    }
+}
+
+fn decode_image(image: &image::Data, srgb: bool) -> ImageTexture {
+    use image::Format;
+
+    let (channels, bytes_per_channel) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+
+    let channel = |bytes: &[u8]| -> f64 {
+        let value = match bytes_per_channel {
+            1 => bytes[0] as f64 / 255.0,
+            2 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64 / 65535.0,
+            // Float images are already linear
+            _ => return f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64,
+        };
+        if srgb {
+            srgb_to_linear(value)
+        } else {
+            value
+        }
+    };
+
+    let pixels = image
+        .pixels
+        .chunks_exact(channels * bytes_per_channel)
+        .map(|pixel| {
+            let c: Vec<f64> = pixel.chunks_exact(bytes_per_channel).map(channel).collect();
+            match channels {
+                1 => Vec3D::new(c[0], c[0], c[0]),
+                2 => Vec3D::new(c[0], c[1], 0.0),
+                _ => Vec3D::new(c[0], c[1], c[2]),
+            }
+        })
+        .collect();
+
+    ImageTexture::new(image.width as usize, image.height as usize, pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ray::Ray, sampler::IndependentSampler};
+
+    // Binary glTF with a single red, blue-glowing triangle at z = -5 and a
+    // camera at z = 1 looking at it
+    fn triangle_glb() -> Vec<u8> {
+        let json = r#"{
+            "asset": {"version": "2.0"},
+            "scene": 0,
+            "scenes": [{"nodes": [0, 1]}],
+            "nodes": [
+                {"mesh": 0, "translation": [0, 0, -5]},
+                {"camera": 0, "translation": [0, 0, 1]}
+            ],
+            "cameras": [{"type": "perspective", "perspective": {"yfov": 0.8, "znear": 0.1}}],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0}, "material": 0}]}],
+            "materials": [{
+                "pbrMetallicRoughness": {"baseColorFactor": [1, 0, 0, 1], "metallicFactor": 0},
+                "emissiveFactor": [0, 0, 1]
+            }],
+            "buffers": [{"byteLength": 36}],
+            "bufferViews": [{"buffer": 0, "byteLength": 36}],
+            "accessors": [{
+                "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                "min": [-1, -1, 0], "max": [1, 1, 0]
+            }]
+        }"#;
+        let mut json = json.as_bytes().to_vec();
+        while !json.len().is_multiple_of(4) {
+            json.push(b' ');
+        }
+        let mut bin = Vec::new();
+        for value in [-1.0f32, -1.0, 0.0, 1.0, -1.0, 0.0, 0.0, 1.0, 0.0] {
+            bin.extend(value.to_le_bytes());
+        }
+
+        let mut glb = Vec::new();
+        glb.extend(b"glTF");
+        glb.extend(2u32.to_le_bytes());
+        glb.extend(((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes());
+        glb.extend((json.len() as u32).to_le_bytes());
+        glb.extend(b"JSON");
+        glb.extend(json);
+        glb.extend((bin.len() as u32).to_le_bytes());
+        glb.extend(b"BIN\0");
+        glb.extend(bin);
+        glb
+    }
+
+    #[test]
+    fn test_parse_glb_scene() {
+        let builder = parse(&triangle_glb(), 1.5).unwrap();
+        assert_eq!(builder.object_count(), 1);
+        assert!(builder.camera().is_some());
+
+        let scene = builder.build();
+        let sampler = &mut IndependentSampler::new(1, 0);
+        let origin = scene.camera.get_ray(0.5, 0.5, sampler).unwrap().origin;
+        assert_eq!(origin, Point3D::new(0.0, 0.0, 1.0));
+
+        let ray = Ray::new(origin, Vec3D::new(0.0, 0.0, -1.0), 0.0);
+        let hit = scene.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 6.0).abs() < 1.0e-9);
+        assert_eq!(
+            scene.material(hit.material).emitted(&hit).to_vec3d(),
+            Vec3D::new(0.0, 0.0, 1.0)
+        );
+    }
+}
@@ -0,0 +1,946 @@
+// Subset of the pbrt-v3 scene format: a perspective camera placed with
+// `LookAt` or the transform directives, film resolution, pixel sample count,
+// spheres and triangle meshes (inline or from PLY files), object instancing,
+// matte, metal and glass materials, and diffuse area lights.
+//
+// Anything else is skipped and reported as a warning with its line number,
+// so large test scenes still load with whatever this crate can render.
+
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    camera::{FieldOfView, OrthographicCamera, PerspectiveCamera},
+    color::Color,
+    hit::Hit,
+    material::{Dielectric, DiffuseLight, Lambertian, MaterialId, Metal, Scatter},
+    mesh::{ply, MeshData, TriangleMesh},
+    object::{Instance, Sphere},
+    scene::SceneBuilder,
+    vec::{Matrix4, Point3D, Transform, Vec3D},
+};
+
+pub struct PbrtScene {
+    /// Objects, area lights and camera, ready to `build`.
+    pub scene: SceneBuilder,
+    pub width: usize,
+    pub height: usize,
+    pub samples_per_pixel: usize,
+    /// Directives, types and parameters that were skipped.
+    pub warnings: Vec<String>,
+}
+
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<PbrtScene> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path)?;
+    parse(&source, path.parent().unwrap_or_else(|| Path::new("")))
+}
+
+/// Parses a scene held in memory; `plymesh` files are looked up relative to
+/// `base`.
+pub fn parse(source: &str, base: &Path) -> io::Result<PbrtScene> {
+    Parser::new(tokenize(source)?, base).parse()
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Directive(String),
+    String(String),
+    Number(f64),
+    Open,
+    Close,
+}
+
+fn tokenize(source: &str) -> io::Result<Vec<(Token, usize)>> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+
+    while let Some(c) = chars.next() {
+        let token = match c {
+            '\n' => {
+                line += 1;
+                continue;
+            }
+            '#' => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            '[' => Token::Open,
+            ']' => Token::Close,
+            '"' => {
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\n') | None => {
+                            return Err(invalid(format!("line {}: unterminated string", line)))
+                        }
+                        Some(c) => string.push(c),
+                    }
+                }
+                Token::String(string)
+            }
+            c => {
+                let mut word = c.to_string();
+                while let Some(c) =
+                    chars.next_if(|&c| !c.is_whitespace() && !matches!(c, '"' | '[' | ']' | '#'))
+                {
+                    word.push(c);
+                }
+                match word.parse() {
+                    Ok(number) => Token::Number(number),
+                    Err(_) => Token::Directive(word),
+                }
+            }
+        };
+        tokens.push((token, line));
+    }
+
+    Ok(tokens)
+}
+
+enum Value {
+    Numbers(Vec<f64>),
+    Strings(Vec<String>),
+}
+
+struct Param {
+    kind: String,
+    name: String,
+    value: Value,
+    used: Cell<bool>,
+}
+
+/// Parameter list of a directive. Lookups mark parameters as used so the
+/// rest can be reported.
+#[derive(Default)]
+struct ParamSet {
+    params: Vec<Param>,
+}
+
+impl ParamSet {
+    fn find(&self, kinds: &[&str], name: &str) -> Option<&Value> {
+        let param = self
+            .params
+            .iter()
+            .find(|param| param.name == name && kinds.contains(&param.kind.as_str()))?;
+        param.used.set(true);
+        Some(&param.value)
+    }
+
+    fn numbers(&self, kinds: &[&str], name: &str) -> Option<&[f64]> {
+        match self.find(kinds, name)? {
+            Value::Numbers(numbers) => Some(numbers),
+            Value::Strings(_) => None,
+        }
+    }
+
+    fn float(&self, name: &str, default: f64) -> f64 {
+        self.numbers(&["float"], name)
+            .and_then(|numbers| numbers.first().copied())
+            .unwrap_or(default)
+    }
+
+    fn integer(&self, name: &str, default: usize, line: usize) -> io::Result<usize> {
+        match self
+            .numbers(&["integer"], name)
+            .and_then(|numbers| numbers.first())
+        {
+            Some(&number) => to_index(number, line),
+            None => Ok(default),
+        }
+    }
+
+    fn string(&self, name: &str) -> Option<&str> {
+        match self.find(&["string"], name)? {
+            Value::Strings(strings) => strings.first().map(String::as_str),
+            Value::Numbers(_) => None,
+        }
+    }
+
+    fn boolean(&self, name: &str, default: bool) -> bool {
+        match self.find(&["bool"], name) {
+            Some(Value::Strings(strings)) => strings.first().map_or(default, |s| s == "true"),
+            _ => default,
+        }
+    }
+
+    fn rgb(&self, name: &str, default: Color) -> Color {
+        match self.numbers(&["rgb", "color"], name) {
+            Some(&[r, g, b]) => Color::new(r, g, b),
+            _ => default,
+        }
+    }
+
+    fn unused(&self) -> impl Iterator<Item = &Param> {
+        self.params.iter().filter(|param| !param.used.get())
+    }
+}
+
+#[derive(Clone)]
+struct GraphicsState {
+    transform: Transform,
+    material: MaterialId,
+    area_light: Option<MaterialId>,
+}
+
+struct Parser<'a> {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+    base: &'a Path,
+    state: GraphicsState,
+    attributes: Vec<GraphicsState>,
+    transforms: Vec<Transform>,
+    camera: Option<(Transform, String, ParamSet, usize)>,
+    width: usize,
+    height: usize,
+    samples_per_pixel: usize,
+    scene: SceneBuilder,
+    // Shapes between `ObjectBegin` and `ObjectEnd` go to the object being
+    // defined instead of the scene
+    object: Option<(String, SceneBuilder)>,
+    objects: HashMap<String, Arc<dyn Hit>>,
+    default_material: MaterialId,
+    warnings: Vec<String>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(tokens: Vec<(Token, usize)>, base: &'a Path) -> Self {
+        let mut scene = SceneBuilder::new();
+        let default_material =
+            scene.add_material(Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+        Self {
+            tokens,
+            position: 0,
+            base,
+            state: GraphicsState {
+                transform: Transform::identity(),
+                material: default_material,
+                area_light: None,
+            },
+            attributes: Vec::new(),
+            transforms: Vec::new(),
+            camera: None,
+            // pbrt's defaults
+            width: 1280,
+            height: 720,
+            samples_per_pixel: 16,
+            scene,
+            object: None,
+            objects: HashMap::new(),
+            default_material,
+            warnings: Vec::new(),
+        }
+    }
+
+    fn parse(mut self) -> io::Result<PbrtScene> {
+        while let Some((token, line)) = self.next() {
+            let directive = match token {
+                Token::Directive(directive) => directive,
+                other => {
+                    return Err(invalid(format!(
+                        "line {}: expected a directive, found {:?}",
+                        line, other
+                    )))
+                }
+            };
+            self.directive(&directive, line)?;
+        }
+
+        self.set_camera();
+        Ok(PbrtScene {
+            scene: self.scene,
+            width: self.width,
+            height: self.height,
+            samples_per_pixel: self.samples_per_pixel,
+            warnings: self.warnings,
+        })
+    }
+
+    fn directive(&mut self, directive: &str, line: usize) -> io::Result<()> {
+        match directive {
+            "LookAt" => {
+                let v = self.numbers(9, line)?;
+                let look_at = look_at(
+                    Point3D::new(v[0], v[1], v[2]),
+                    Point3D::new(v[3], v[4], v[5]),
+                    Vec3D::new(v[6], v[7], v[8]),
+                )
+                .ok_or_else(|| invalid(format!("line {}: degenerate LookAt", line)))?;
+                self.apply(look_at);
+            }
+            "Translate" => {
+                let v = self.numbers(3, line)?;
+                self.apply(Transform::translate(Vec3D::new(v[0], v[1], v[2])));
+            }
+            "Scale" => {
+                let v = self.numbers(3, line)?;
+                self.apply(Transform::scale(Vec3D::new(v[0], v[1], v[2])));
+            }
+            "Rotate" => {
+                let v = self.numbers(4, line)?;
+                self.apply(Transform::rotate(Vec3D::new(v[1], v[2], v[3]), v[0]));
+            }
+            "Identity" => self.state.transform = Transform::identity(),
+            "Transform" => {
+                let v = self.numbers(16, line)?;
+                self.state.transform = matrix_transform(&v, line)?;
+            }
+            "ConcatTransform" => {
+                let v = self.numbers(16, line)?;
+                let transform = matrix_transform(&v, line)?;
+                self.apply(transform);
+            }
+            "Camera" => {
+                let (kind, params) = self.typed_params(line)?;
+                if kind != "perspective" && kind != "orthographic" {
+                    self.warn(
+                        line,
+                        format!("unsupported camera `{}`, using perspective", kind),
+                    );
+                }
+                // The current transform maps world to camera space
+                self.camera = Some((self.state.transform.inverse(), kind, params, line));
+            }
+            "Film" => {
+                let (_, params) = self.typed_params(line)?;
+                self.width = params.integer("xresolution", self.width, line)?;
+                self.height = params.integer("yresolution", self.height, line)?;
+                if self.width == 0 || self.height == 0 {
+                    return Err(invalid(format!("line {}: empty film resolution", line)));
+                }
+                self.warn_unused(line, &params);
+            }
+            "Sampler" => {
+                let (_, params) = self.typed_params(line)?;
+                self.samples_per_pixel =
+                    params.integer("pixelsamples", self.samples_per_pixel, line)?;
+                if self.samples_per_pixel == 0 {
+                    return Err(invalid(format!("line {}: zero pixel samples", line)));
+                }
+                self.warn_unused(line, &params);
+            }
+            "WorldBegin" => self.state.transform = Transform::identity(),
+            "WorldEnd" => {}
+            "AttributeBegin" => self.attributes.push(self.state.clone()),
+            "AttributeEnd" => match self.attributes.pop() {
+                Some(state) => self.state = state,
+                None => self.warn(line, "unmatched AttributeEnd"),
+            },
+            "TransformBegin" => self.transforms.push(self.state.transform),
+            "TransformEnd" => match self.transforms.pop() {
+                Some(transform) => self.state.transform = transform,
+                None => self.warn(line, "unmatched TransformEnd"),
+            },
+            "Material" => {
+                let (kind, params) = self.typed_params(line)?;
+                self.state.material = self.material(&kind, &params, line);
+                self.warn_unused(line, &params);
+            }
+            "AreaLightSource" => {
+                let (kind, params) = self.typed_params(line)?;
+                if kind != "diffuse" {
+                    self.warn(line, format!("unsupported area light `{}`", kind));
+                }
+                let mut light = DiffuseLight::new(params.rgb("L", Color::White));
+                if params.boolean("twosided", false) {
+                    light = light.with_two_sided();
+                }
+                self.state.area_light = Some(self.scene.add_material(Arc::new(light)));
+                self.warn_unused(line, &params);
+            }
+            "Shape" => {
+                let (kind, params) = self.typed_params(line)?;
+                self.shape(&kind, &params, line)?;
+                self.warn_unused(line, &params);
+            }
+            "ObjectBegin" => {
+                let (name, _) = self.typed_params(line)?;
+                if self.object.is_some() {
+                    return Err(invalid(format!(
+                        "line {}: ObjectBegin inside an object",
+                        line
+                    )));
+                }
+                // Objects keep their shapes' transforms, relative to where
+                // they are instanced
+                self.attributes.push(self.state.clone());
+                self.object = Some((name, SceneBuilder::new()));
+            }
+            "ObjectEnd" => match self.object.take() {
+                Some((name, object)) => {
+                    let object: Arc<dyn Hit> = Arc::new(object.build());
+                    self.objects.insert(name, object);
+                    if let Some(state) = self.attributes.pop() {
+                        self.state = state;
+                    }
+                }
+                None => self.warn(line, "unmatched ObjectEnd"),
+            },
+            "ObjectInstance" => {
+                let (name, _) = self.typed_params(line)?;
+                if self.object.is_some() {
+                    return Err(invalid(format!(
+                        "line {}: ObjectInstance inside an object",
+                        line
+                    )));
+                }
+                match self.objects.get(&name) {
+                    Some(object) => {
+                        let instance = Instance::new(object.clone(), self.state.transform);
+                        self.scene.add(instance);
+                    }
+                    None => self.warn(line, format!("unknown object `{}`", name)),
+                }
+            }
+            _ => {
+                // Skip the arguments up to the next directive
+                while matches!(self.peek(), Some(token) if !matches!(token, Token::Directive(_))) {
+                    self.position += 1;
+                }
+                self.warn(line, format!("unsupported directive `{}`", directive));
+            }
+        }
+        Ok(())
+    }
+
+    fn material(&mut self, kind: &str, params: &ParamSet, line: usize) -> MaterialId {
+        let material: Arc<dyn Scatter> = match kind {
+            "matte" => Arc::new(Lambertian::new(params.rgb("Kd", Color::new(0.5, 0.5, 0.5)))),
+            "metal" => {
+                // Normal-incidence Fresnel reflectance of a conductor, defaulting to copper
+                let eta = params.rgb("eta", Color::new(0.2004, 0.9240, 1.1022));
+                let k = params.rgb("k", Color::new(3.9129, 2.4528, 2.1421));
+                let (eta, k) = (eta.to_vec3d(), k.to_vec3d());
+                let r = |i: usize| {
+                    ((eta[i] - 1.0).powi(2) + k[i].powi(2))
+                        / ((eta[i] + 1.0).powi(2) + k[i].powi(2))
+                };
+                Arc::new(Metal::new(
+                    Color::new(r(0), r(1), r(2)),
+                    params.float("roughness", 0.01),
+                ))
+            }
+            "glass" => Arc::new(Dielectric::new(params.float("index", 1.5))),
+            _ => {
+                self.warn(
+                    line,
+                    format!("unsupported material `{}`, using matte", kind),
+                );
+                return self.default_material;
+            }
+        };
+        self.scene.add_material(material)
+    }
+
+    fn shape(&mut self, kind: &str, params: &ParamSet, line: usize) -> io::Result<()> {
+        if self.object.is_some() && self.state.area_light.is_some() {
+            // As in pbrt, instanced shapes can't be lights
+            self.warn(line, "area light inside an object, using its material");
+            self.state.area_light = None;
+        }
+        let is_light = self.state.area_light.is_some();
+        let material = self.state.area_light.unwrap_or(self.state.material);
+
+        let data = match kind {
+            "sphere" => {
+                let sphere = Sphere::new(
+                    Point3D::new(0.0, 0.0, 0.0),
+                    params.float("radius", 1.0),
+                    material,
+                );
+                self.add(
+                    Instance::new(Arc::new(sphere), self.state.transform),
+                    is_light,
+                );
+                return Ok(());
+            }
+            "trianglemesh" => triangle_mesh(params, line)?,
+            "plymesh" => {
+                let filename = params
+                    .string("filename")
+                    .ok_or_else(|| invalid(format!("line {}: plymesh without a filename", line)))?;
+                let path: PathBuf = self.base.join(filename);
+                ply::load(&path).map_err(|error| {
+                    io::Error::new(error.kind(), format!("{}: {}", path.display(), error))
+                })?
+            }
+            _ => {
+                self.warn(line, format!("unsupported shape `{}`", kind));
+                return Ok(());
+            }
+        };
+
+        let data = to_world_space(data, &self.state.transform);
+        self.add(TriangleMesh::new(Arc::new(data), material), is_light);
+        Ok(())
+    }
+
+    fn add<H: Hit + 'static>(&mut self, object: H, is_light: bool) {
+        if let Some((_, builder)) = &mut self.object {
+            builder.add(object);
+        } else if is_light {
+            self.scene.add_light(object);
+        } else {
+            self.scene.add(object);
+        }
+    }
+
+    fn set_camera(&mut self) {
+        let (camera_to_world, kind, params, line) = self.camera.take().unwrap_or_else(|| {
+            let kind = "perspective".to_string();
+            (Transform::identity(), kind, ParamSet::default(), 0)
+        });
+        let aspect_ratio = self.width as f64 / self.height as f64;
+
+        let eye = camera_to_world.point(Point3D::new(0.0, 0.0, 0.0));
+        let forward = camera_to_world.vector(Vec3D::new(0.0, 0.0, 1.0));
+        let up = camera_to_world.vector(Vec3D::new(0.0, 1.0, 0.0));
+        let right = camera_to_world.vector(Vec3D::new(1.0, 0.0, 0.0));
+
+        if kind == "orthographic" {
+            // The screen window is in camera space units, by default 2
+            // across the shorter image axis
+            let (x0, x1, y0, y1) = match params.numbers(&["float"], "screenwindow") {
+                Some(&[x0, x1, y0, y1]) => (x0, x1, y0, y1),
+                _ if aspect_ratio >= 1.0 => (-aspect_ratio, aspect_ratio, -1.0, 1.0),
+                _ => (-1.0, 1.0, -1.0 / aspect_ratio, 1.0 / aspect_ratio),
+            };
+            let center = eye + (x0 + x1) / 2.0 * right + (y0 + y1) / 2.0 * up;
+            let mut camera =
+                OrthographicCamera::new(center, center + forward, up, x1 - x0, y1 - y0);
+            // Mirrored like the perspective camera below
+            if camera.horizontal.dot(right) < 0.0 {
+                camera.lower_left_corner = camera.lower_left_corner + camera.horizontal;
+                camera.horizontal = -1.0 * camera.horizontal;
+            }
+            self.warn_unused(line, &params);
+            self.scene.set_camera(camera);
+            return;
+        }
+
+        // pbrt's field of view spans the shorter image axis
+        let fov = params.float("fov", 90.0);
+        let fov = if aspect_ratio >= 1.0 {
+            FieldOfView::Vertical(fov)
+        } else {
+            FieldOfView::Horizontal(fov)
+        };
+
+        let mut camera = PerspectiveCamera::new(
+            eye,
+            eye + forward,
+            up,
+            fov,
+            aspect_ratio,
+            2.0 * params.float("lensradius", 0.0),
+            params.float("focaldistance", 1.0e6),
+        );
+
+        // pbrt's camera space is left-handed, so unless the scene mirrors it
+        // the image runs the other way across
+        if camera.cu.dot(right) < 0.0 {
+            camera.lower_left_corner = camera.lower_left_corner + camera.horizontal;
+            camera.horizontal = -1.0 * camera.horizontal;
+            camera.cu = -1.0 * camera.cu;
+        }
+
+        self.warn_unused(line, &params);
+        self.scene.set_camera(camera);
+    }
+
+    fn apply(&mut self, transform: Transform) {
+        self.state.transform = self.state.transform * transform;
+    }
+
+    fn warn(&mut self, line: usize, message: impl Into<String>) {
+        self.warnings
+            .push(format!("line {}: {}", line, message.into()));
+    }
+
+    fn warn_unused(&mut self, line: usize, params: &ParamSet) {
+        for param in params.unused() {
+            self.warnings.push(format!(
+                "line {}: unsupported parameter \"{} {}\"",
+                line, param.kind, param.name
+            ));
+        }
+    }
+
+    fn next(&mut self) -> Option<(Token, usize)> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    /// Reads `count` numbers, optionally wrapped in brackets.
+    fn numbers(&mut self, count: usize, line: usize) -> io::Result<Vec<f64>> {
+        let bracketed = self.peek() == Some(&Token::Open);
+        if bracketed {
+            self.position += 1;
+        }
+
+        let mut numbers = Vec::with_capacity(count);
+        while numbers.len() < count || bracketed {
+            match self.peek() {
+                Some(Token::Number(number)) => numbers.push(*number),
+                Some(Token::Close) if bracketed => {
+                    self.position += 1;
+                    break;
+                }
+                _ => break,
+            }
+            self.position += 1;
+        }
+
+        if numbers.len() != count {
+            return Err(invalid(format!(
+                "line {}: expected {} numbers, found {}",
+                line,
+                count,
+                numbers.len()
+            )));
+        }
+        Ok(numbers)
+    }
+
+    /// Reads the quoted type name and parameter list of a directive.
+    fn typed_params(&mut self, line: usize) -> io::Result<(String, ParamSet)> {
+        let kind = match self.next() {
+            Some((Token::String(kind), _)) => kind,
+            _ => return Err(invalid(format!("line {}: expected a quoted type", line))),
+        };
+
+        let mut params = ParamSet::default();
+        while let Some(Token::String(declaration)) = self.peek() {
+            let mut words = declaration.split_whitespace();
+            let (kind, name) = match (words.next(), words.next(), words.next()) {
+                (Some(kind), Some(name), None) => (kind.to_string(), name.to_string()),
+                _ => {
+                    return Err(invalid(format!(
+                        "line {}: malformed parameter \"{}\"",
+                        line, declaration
+                    )))
+                }
+            };
+            self.position += 1;
+            let value = self.value(line)?;
+            params.params.push(Param {
+                kind,
+                name,
+                value,
+                used: Cell::new(false),
+            });
+        }
+
+        Ok((kind, params))
+    }
+
+    fn value(&mut self, line: usize) -> io::Result<Value> {
+        let mut numbers = Vec::new();
+        let mut strings = Vec::new();
+        let bracketed = self.peek() == Some(&Token::Open);
+        if bracketed {
+            self.position += 1;
+        }
+
+        loop {
+            match self.next() {
+                Some((Token::Number(number), _)) => numbers.push(number),
+                Some((Token::String(string), _)) => strings.push(string),
+                Some((Token::Close, _)) if bracketed => break,
+                _ => return Err(invalid(format!("line {}: malformed parameter value", line))),
+            }
+            if !bracketed {
+                break;
+            }
+        }
+
+        match (numbers.is_empty(), strings.is_empty()) {
+            (_, true) => Ok(Value::Numbers(numbers)),
+            (true, false) => Ok(Value::Strings(strings)),
+            (false, false) => Err(invalid(format!(
+                "line {}: parameter mixes numbers and strings",
+                line
+            ))),
+        }
+    }
+}
+
+// Integer parameters are read as numbers, so check they are whole and
+// non-negative before using them as counts or indices
+fn to_index(number: f64, line: usize) -> io::Result<usize> {
+    if number >= 0.0 && number.fract() == 0.0 {
+        Ok(number as usize)
+    } else {
+        Err(invalid(format!(
+            "line {}: expected a non-negative integer, found {}",
+            line, number
+        )))
+    }
+}
+
+// World-to-camera transform, with the camera looking down +z as in pbrt
+fn look_at(eye: Point3D, target: Point3D, up: Vec3D) -> Option<Transform> {
+    let direction = (target - eye).normalize();
+    let right = up.normalize().cross(direction);
+    if right.is_near_zero() {
+        return None;
+    }
+    let right = right.normalize();
+    let up = direction.cross(right);
+
+    let camera_to_world = Matrix4::new([
+        [right.x, up.x, direction.x, eye.x],
+        [right.y, up.y, direction.y, eye.y],
+        [right.z, up.z, direction.z, eye.z],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+    Some(Transform::new(camera_to_world)?.inverse())
+}
+
+// pbrt lists matrices column by column
+fn matrix_transform(v: &[f64], line: usize) -> io::Result<Transform> {
+    let mut m = [[0.0; 4]; 4];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = v[j * 4 + i];
+        }
+    }
+    Transform::new(Matrix4::new(m))
+        .ok_or_else(|| invalid(format!("line {}: singular transform", line)))
+}
+
+fn triangle_mesh(params: &ParamSet, line: usize) -> io::Result<MeshData> {
+    let positions: Vec<Point3D> = params
+        .numbers(&["point", "point3"], "P")
+        .unwrap_or_default()
+        .chunks_exact(3)
+        .map(|p| Point3D::new(p[0], p[1], p[2]))
+        .collect();
+    let normals = params
+        .numbers(&["normal", "normal3"], "N")
+        .unwrap_or_default()
+        .chunks_exact(3)
+        .map(|n| Vec3D::new(n[0], n[1], n[2]))
+        .collect();
+    let uvs = params
+        .numbers(&["float", "point2"], "uv")
+        .or_else(|| params.numbers(&["float", "point2"], "st"))
+        .unwrap_or_default()
+        .chunks_exact(2)
+        .map(|uv| (uv[0], uv[1]))
+        .collect();
+
+    let indices: Vec<[usize; 3]> = match params.numbers(&["integer"], "indices") {
+        Some(indices) => indices
+            .chunks_exact(3)
+            .map(|i| {
+                Ok([
+                    to_index(i[0], line)?,
+                    to_index(i[1], line)?,
+                    to_index(i[2], line)?,
+                ])
+            })
+            .collect::<io::Result<_>>()?,
+        // A lone triangle may leave its indices out
+        None if positions.len() == 3 => vec![[0, 1, 2]],
+        None => {
+            return Err(invalid(format!(
+                "line {}: trianglemesh without indices",
+                line
+            )))
+        }
+    };
+    if indices
+        .iter()
+        .flatten()
+        .any(|&index| index >= positions.len())
+    {
+        return Err(invalid(format!(
+            "line {}: trianglemesh index out of range",
+            line
+        )));
+    }
+
+    Ok(MeshData {
+        positions,
+        normals,
+        uvs,
+        indices,
+        ..MeshData::default()
+    })
+}
+
+// Meshes are baked into world space rather than instanced, as pbrt does
+fn to_world_space(mut data: MeshData, transform: &Transform) -> MeshData {
+    for position in &mut data.positions {
+        *position = transform.point(*position);
+    }
+    for normal in &mut data.normals {
+        *normal = transform.normal(*normal).normalize();
+    }
+
+    // A mirroring transform flips the winding, and with it which side faces out
+    let x = transform.vector(Vec3D::new(1.0, 0.0, 0.0));
+    let y = transform.vector(Vec3D::new(0.0, 1.0, 0.0));
+    let z = transform.vector(Vec3D::new(0.0, 0.0, 1.0));
+    if x.cross(y).dot(z) < 0.0 {
+        for triangle in &mut data.indices {
+            triangle.swap(1, 2);
+        }
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ray::Ray, sampler::IndependentSampler};
+
+    const SCENE: &str = r#"
+        LookAt 0 0 5  0 0 0  0 1 0
+        Camera "perspective" "float fov" [45]
+        Film "image" "integer xresolution" [200] "integer yresolution" [100]
+        Sampler "halton" "integer pixelsamples" 8
+
+        WorldBegin
+        AttributeBegin
+            AreaLightSource "diffuse" "rgb L" [4 4 4]
+            Translate 0 3 0
+            Shape "sphere" "float radius" 0.5
+        AttributeEnd
+
+        Material "glass"
+        Shape "trianglemesh" "integer indices" [0 1 2]
+            "point P" [-1 -1 0  1 -1 0  0 1 0]
+        LightSource "infinite"   # not supported
+        Shape "cylinder"
+        WorldEnd
+    "#;
+
+    #[test]
+    fn test_parse_scene() {
+        let pbrt = parse(SCENE, Path::new("")).unwrap();
+        assert_eq!((pbrt.width, pbrt.height), (200, 100));
+        assert_eq!(pbrt.samples_per_pixel, 8);
+        assert_eq!(pbrt.warnings.len(), 2);
+        assert!(pbrt.warnings[0].starts_with("line 17: unsupported directive `LightSource`"));
+
+        let scene = pbrt.scene.build();
+        assert_eq!(scene.objects().len(), 2);
+        assert_eq!(scene.lights().len(), 1);
+
+        let sampler = &mut IndependentSampler::new(1, 0);
+        let origin = scene.camera.get_ray(0.5, 0.5, sampler).unwrap().origin;
+        assert_eq!(origin, Point3D::new(0.0, 0.0, 5.0));
+        // pbrt looking down -z puts -x on the right of the image
+        let right = scene.camera.get_ray(1.0, 0.5, sampler).unwrap();
+        assert!(right.direction.x < 0.0);
+
+        let ray = Ray::new(origin, Vec3D::new(0.0, 0.0, -1.0), 0.0);
+        let hit = scene.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 5.0).abs() < 1.0e-9);
+
+        let ray = Ray::new(Point3D::new(0.0, 0.0, 0.0), Vec3D::new(0.0, 1.0, 0.0), 0.0);
+        let hit = scene.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 2.5).abs() < 1.0e-9);
+        assert_eq!(
+            scene.material(hit.material).emitted(&hit).to_vec3d(),
+            Vec3D::new(4.0, 4.0, 4.0)
+        );
+    }
+
+    #[test]
+    fn test_orthographic_camera() {
+        let pbrt = parse(
+            r#"
+            LookAt 0 0 5  0 0 0  0 1 0
+            Camera "orthographic" "float screenwindow" [0 4 -1 1]
+            Film "image" "integer xresolution" [200] "integer yresolution" [100]
+            "#,
+            Path::new(""),
+        )
+        .unwrap();
+        assert!(pbrt.warnings.is_empty());
+        let scene = pbrt.scene.build();
+        let sampler = &mut IndependentSampler::new(1, 0);
+        // The window starts at camera space x = 0, on the image's left,
+        // and +x in pbrt's camera space is world -x here
+        let left = scene.camera.get_ray(0.0, 0.5, sampler).unwrap();
+        let right = scene.camera.get_ray(1.0, 0.5, sampler).unwrap();
+        assert_eq!(left.origin, Point3D::new(0.0, 0.0, 5.0));
+        assert_eq!(right.origin, Point3D::new(-4.0, 0.0, 5.0));
+        assert_eq!(right.direction, Vec3D::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn test_object_instances() {
+        let pbrt = parse(
+            r#"
+            WorldBegin
+            ObjectBegin "ball"
+                Translate 0 0 -1
+                Shape "sphere" "float radius" 0.5
+            ObjectEnd
+            AttributeBegin
+                Translate -2 0 -5
+                ObjectInstance "ball"
+            AttributeEnd
+            AttributeBegin
+                Translate 2 0 -5
+                ObjectInstance "ball"
+            AttributeEnd
+            ObjectInstance "missing"
+            WorldEnd
+            "#,
+            Path::new(""),
+        )
+        .unwrap();
+        assert_eq!(pbrt.warnings.len(), 1);
+        assert!(pbrt.warnings[0].contains("unknown object `missing`"));
+
+        let scene = pbrt.scene.build();
+        assert_eq!(scene.objects().len(), 2);
+        let down = |x: f64| Ray::new(Point3D::new(x, 0.0, 0.0), Vec3D::new(0.0, 0.0, -1.0), 0.0);
+        // The prototype itself isn't in the scene, only its instances
+        assert!(scene.hit(&down(0.0), 0.001, f64::INFINITY).is_none());
+        for x in [-2.0, 2.0] {
+            let hit = scene.hit(&down(x), 0.001, f64::INFINITY).unwrap();
+            assert!((hit.t - 5.5).abs() < 1.0e-9);
+        }
+    }
+
+    #[test]
+    fn test_reject_bad_integers() {
+        for source in [
+            r#"Film "image" "integer xresolution" [-200]"#,
+            r#"Film "image" "integer yresolution" [0]"#,
+            r#"Sampler "halton" "integer pixelsamples" 2.5"#,
+            r#"Shape "trianglemesh" "integer indices" [0 -1 2] "point P" [0 0 0 1 0 0 0 1 0]"#,
+        ] {
+            let error = parse(source, Path::new("")).err().unwrap();
+            assert!(error.to_string().starts_with("line 1:"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_unterminated_string() {
+        assert!(parse("Shape \"sphere", Path::new("")).is_err());
+    }
+}
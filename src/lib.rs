@@ -1,31 +1,63 @@
+pub mod bvh;
 pub mod camera;
+mod checkpoint;
 pub mod color;
+pub mod content;
+pub mod csg;
+pub mod film;
+pub mod import;
 pub mod material;
+pub mod mesh;
+pub mod poly;
+pub mod progress;
+pub mod random;
 pub mod ray;
+pub mod render;
+pub mod sampler;
+pub mod scene;
+pub mod sdf;
+pub mod texture;
+pub mod tile;
 pub mod vec;
 
 pub mod hit {
     use std::sync::Arc;
 
     use crate::{
-        material::Scatter,
+        bvh::Aabb,
+        material::MaterialId,
+        mesh::MeshData,
         ray::Ray,
         vec::{Point3D, Vec3D},
     };
 
+    /// Triangle of a mesh that was hit and the barycentric coordinates of
+    /// the hit point, for looking up per-vertex data.
+    #[derive(Clone)]
+    pub struct TriangleHit {
+        pub mesh: Arc<MeshData>,
+        pub index: usize,
+        pub b1: f64,
+        pub b2: f64,
+    }
+
     pub struct HitRecord {
         pub hit_point: Point3D,
         pub normal: Vec3D,
-        pub material: Arc<dyn Scatter>,
+        pub material: MaterialId,
         pub t: f64,
         pub front_face: bool,
+        pub u: f64,
+        pub v: f64,
+        pub tangent: Option<Vec3D>,
+        pub triangle: Option<TriangleHit>,
     }
 
     impl HitRecord {
         pub fn new(
             hit_point: Point3D,
             normal: Vec3D,
-            material: Arc<dyn Scatter>,
+            material: MaterialId,
             t: f64,
             ray: &Ray,
         ) -> Self {
@@ -37,88 +69,1180 @@ pub mod hit {
                 material,
                 t,
                 front_face,
+                u: 0.0,
+                v: 0.0,
+                tangent: None,
+                triangle: None,
             }
         }
+
+        pub fn with_uv(mut self, u: f64, v: f64) -> Self {
+            self.u = u;
+            self.v = v;
+            self
+        }
+
+        /// Direction of increasing `u` along the surface, for normal mapping.
+        pub fn with_tangent(mut self, tangent: Vec3D) -> Self {
+            self.tangent = Some(tangent);
+            self
+        }
+
+        pub fn with_triangle(mut self, triangle: TriangleHit) -> Self {
+            self.triangle = Some(triangle);
+            self
+        }
+    }
+
+    /// Stretch of a ray that lies inside a closed object.
+    pub struct Span {
+        pub enter: HitRecord,
+        pub exit: HitRecord,
     }
 
     pub trait Hit: Send + Sync {
         fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
+
+        /// Distance to the same hit `hit` would find, without filling in the
+        /// rest of the record. Used to find the nearest of many objects before
+        /// paying for surface data on just that one.
+        fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<f64> {
+            self.hit(ray, t_min, t_max).map(|hit_record| hit_record.t)
+        }
+
+        /// Box enclosing the object, or `None` for objects that are unbounded
+        /// or impractical to bound, which are then tested against every ray.
+        fn bounding_box(&self) -> Option<Aabb> {
+            None
+        }
+
+        /// Adds everything that decides the object's shape, placement and
+        /// materials to `values`, for `Scene::content_hash`.
+        fn hash_into(&self, values: &mut Vec<u64>);
+
+        /// Every span where the infinite line through `ray` is inside the
+        /// object, sorted by `t`. Only meaningful for closed objects.
+        ///
+        /// The default walks the surface one `hit` at a time and pairs up
+        /// front-facing entries with the exits that follow them.
+        fn spans(&self, ray: &Ray) -> Vec<Span> {
+            let mut spans = Vec::new();
+            let mut enter = None;
+            let mut t_min = f64::NEG_INFINITY;
+
+            while let Some(hit_record) = self.hit(ray, t_min, f64::INFINITY) {
+                t_min = hit_record.t + 1.0e-9 * (1.0 + hit_record.t.abs());
+                if hit_record.front_face {
+                    enter = Some(hit_record);
+                } else if let Some(enter) = enter.take() {
+                    spans.push(Span {
+                        enter,
+                        exit: hit_record,
+                    });
+                }
+            }
+
+            spans
+        }
     }
 }
 
 pub mod object {
-    use std::sync::Arc;
+    use std::{f64::consts::PI, sync::Arc};
 
     use crate::{
-        hit::{Hit, HitRecord},
-        material::Scatter,
+        bvh::Aabb,
+        content::HashInto,
+        hit::{Hit, HitRecord, Span},
+        material::MaterialId,
+        poly::{solve_quadratic, solve_quartic},
         ray::Ray,
-        vec::Point3D,
+        vec::{AnimatedTransform, Basis, Point3D, Transform, Vec3D},
     };
 
     #[derive(Clone)]
     pub struct Sphere {
         pub center: Point3D,
         pub radius: f64,
-        pub material: Arc<dyn Scatter>,
+        pub material: MaterialId,
     }
 
     impl Sphere {
-        pub fn new(center: Point3D, radius: f64, material: Arc<dyn Scatter>) -> Self {
+        pub fn new(center: Point3D, radius: f64, material: MaterialId) -> Self {
             Self {
                 center,
                 radius,
                 material,
             }
         }
+
+        // Latitude-longitude coordinates of a point on the unit sphere
+        fn uv(normal: Vec3D) -> (f64, f64) {
+            let theta = (-normal.y).acos();
+            let phi = (-normal.z).atan2(normal.x) + PI;
+            (phi / (2.0 * PI), theta / PI)
+        }
     }
 
     impl Hit for Sphere {
         fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-            let oc = ray.origin - self.center;
-            let a = ray.direction.length().powi(2);
-            let half_b = oc.dot(ray.direction);
-            let c = oc.length().powi(2) - self.radius.powi(2);
-            let discriminant = half_b.powi(2) - a * c;
+            hit_sphere(self.center, self.radius, self.material, ray, t_min, t_max)
+        }
+
+        fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<f64> {
+            intersect_sphere(self.center, self.radius, ray, t_min, t_max)
+        }
+
+        fn bounding_box(&self) -> Option<Aabb> {
+            Some(sphere_box(self.center, self.radius))
+        }
+
+        fn spans(&self, ray: &Ray) -> Vec<Span> {
+            sphere_spans(self.center, self.radius, self.material, ray)
+        }
+
+        fn hash_into(&self, values: &mut Vec<u64>) {
+            ("sphere", self.center, self.radius, self.material).hash_into(values);
+        }
+    }
+
+    fn sphere_box(center: Point3D, radius: f64) -> Aabb {
+        let r = radius.abs();
+        Aabb::new(center - Vec3D::new(r, r, r), center + Vec3D::new(r, r, r))
+    }
+
+    fn hit_sphere(
+        center: Point3D,
+        radius: f64,
+        material: MaterialId,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<HitRecord> {
+        let t = intersect_sphere(center, radius, ray, t_min, t_max)?;
+        Some(sphere_record(center, radius, material, ray, t))
+    }
+
+    fn intersect_sphere(
+        center: Point3D,
+        radius: f64,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<f64> {
+        let oc = ray.origin - center;
+        let a = ray.direction.length().powi(2);
+        let half_b = oc.dot(ray.direction);
+        let c = oc.length().powi(2) - radius.powi(2);
+        let discriminant = half_b.powi(2) - a * c;
+
+        if discriminant < 0.0 {
+            return None;
+        }
 
-            if discriminant < 0.0 {
+        let sqrt_discriminant = discriminant.sqrt();
+        let mut t = (-half_b - sqrt_discriminant) / a;
+        if t < t_min || t_max < t {
+            t = (-half_b + sqrt_discriminant) / a;
+            if t < t_min || t_max < t {
                 return None;
             }
+        }
+
+        Some(t)
+    }
+
+    fn sphere_spans(center: Point3D, radius: f64, material: MaterialId, ray: &Ray) -> Vec<Span> {
+        let oc = ray.origin - center;
+        let a = ray.direction.length().powi(2);
+        let b = 2.0 * oc.dot(ray.direction);
+        let c = oc.length().powi(2) - radius.powi(2);
+
+        match solve_quadratic(a, b, c) {
+            // A negative radius turns the sphere inside out, so it has no interior
+            Some((t0, t1)) if t0 < t1 && radius > 0.0 => vec![Span {
+                enter: sphere_record(center, radius, material, ray, t0),
+                exit: sphere_record(center, radius, material, ray, t1),
+            }],
+            _ => Vec::new(),
+        }
+    }
+
+    fn sphere_record(
+        center: Point3D,
+        radius: f64,
+        material: MaterialId,
+        ray: &Ray,
+        t: f64,
+    ) -> HitRecord {
+        let hit_point = ray.at(t);
+        let normal = (hit_point - center) / radius;
+        let (u, v) = Sphere::uv(normal);
+        HitRecord::new(hit_point, normal, material, t, ray).with_uv(u, v)
+    }
+
+    /// Sphere moving linearly from `center0` at `time0` to `center1` at
+    /// `time1`, and resting at those ends outside that interval.
+    #[derive(Clone)]
+    pub struct MovingSphere {
+        pub center0: Point3D,
+        pub center1: Point3D,
+        pub time0: f64,
+        pub time1: f64,
+        pub radius: f64,
+        pub material: MaterialId,
+    }
+
+    impl MovingSphere {
+        pub fn new(
+            center0: Point3D,
+            center1: Point3D,
+            time0: f64,
+            time1: f64,
+            radius: f64,
+            material: MaterialId,
+        ) -> Self {
+            Self {
+                center0,
+                center1,
+                time0,
+                time1,
+                radius,
+                material,
+            }
+        }
+
+        pub fn center(&self, time: f64) -> Point3D {
+            if self.time1 == self.time0 {
+                return self.center0;
+            }
+            // Clamped so the sphere never leaves its bounding box
+            let t = ((time - self.time0) / (self.time1 - self.time0)).clamp(0.0, 1.0);
+            self.center0 + t * (self.center1 - self.center0)
+        }
+    }
+
+    impl Hit for MovingSphere {
+        fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+            hit_sphere(
+                self.center(ray.time),
+                self.radius,
+                self.material,
+                ray,
+                t_min,
+                t_max,
+            )
+        }
+
+        fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<f64> {
+            intersect_sphere(self.center(ray.time), self.radius, ray, t_min, t_max)
+        }
+
+        fn spans(&self, ray: &Ray) -> Vec<Span> {
+            sphere_spans(self.center(ray.time), self.radius, self.material, ray)
+        }
+
+        fn bounding_box(&self) -> Option<Aabb> {
+            let start = sphere_box(self.center(self.time0), self.radius);
+            let end = sphere_box(self.center(self.time1), self.radius);
+            Some(start.union(&end))
+        }
+
+        fn hash_into(&self, values: &mut Vec<u64>) {
+            (
+                "moving sphere",
+                self.center0,
+                self.center1,
+                self.time0,
+                self.time1,
+                self.radius,
+                self.material,
+            )
+                .hash_into(values);
+        }
+    }
+
+    /// Axis-aligned box spanning the corners `min` and `max`.
+    #[derive(Clone)]
+    pub struct Cuboid {
+        pub min: Point3D,
+        pub max: Point3D,
+        pub material: MaterialId,
+    }
+
+    impl Cuboid {
+        pub fn new(a: Point3D, b: Point3D, material: MaterialId) -> Self {
+            Self {
+                min: Point3D::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+                max: Point3D::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+                material,
+            }
+        }
+
+        // Slab test: the entry and exit `t` along with the axis of the face hit
+        fn slabs(&self, ray: &Ray) -> Option<((f64, usize), (f64, usize))> {
+            let mut enter = (f64::NEG_INFINITY, 0);
+            let mut exit = (f64::INFINITY, 0);
+
+            for axis in 0..3 {
+                let inv_d = 1.0 / ray.direction[axis];
+                let mut t0 = (self.min[axis] - ray.origin[axis]) * inv_d;
+                let mut t1 = (self.max[axis] - ray.origin[axis]) * inv_d;
+                if inv_d < 0.0 {
+                    std::mem::swap(&mut t0, &mut t1);
+                }
+                if t0 > enter.0 {
+                    enter = (t0, axis);
+                }
+                if t1 < exit.0 {
+                    exit = (t1, axis);
+                }
+            }
+
+            if enter.0 > exit.0 || !enter.0.is_finite() || !exit.0.is_finite() {
+                None
+            } else {
+                Some((enter, exit))
+            }
+        }
+
+        fn record(&self, ray: &Ray, t: f64, axis: usize) -> HitRecord {
+            let hit_point = ray.at(t);
+            let extent = self.max - self.min;
+            let local = hit_point - self.min;
+            let on_max = local[axis] > 0.5 * extent[axis];
+
+            let mut normal = [0.0; 3];
+            normal[axis] = if on_max { 1.0 } else { -1.0 };
+            let normal = Vec3D::new(normal[0], normal[1], normal[2]);
+
+            let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+            HitRecord::new(hit_point, normal, self.material, t, ray)
+                .with_uv(local[a] / extent[a], local[b] / extent[b])
+        }
+    }
+
+    impl Hit for Cuboid {
+        fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+            let ((t_enter, enter_axis), (t_exit, exit_axis)) = self.slabs(ray)?;
+            if t_min <= t_enter && t_enter <= t_max {
+                Some(self.record(ray, t_enter, enter_axis))
+            } else if t_min <= t_exit && t_exit <= t_max {
+                Some(self.record(ray, t_exit, exit_axis))
+            } else {
+                None
+            }
+        }
+
+        fn spans(&self, ray: &Ray) -> Vec<Span> {
+            match self.slabs(ray) {
+                Some(((t_enter, enter_axis), (t_exit, exit_axis))) if t_enter < t_exit => {
+                    vec![Span {
+                        enter: self.record(ray, t_enter, enter_axis),
+                        exit: self.record(ray, t_exit, exit_axis),
+                    }]
+                }
+                _ => Vec::new(),
+            }
+        }
+
+        fn bounding_box(&self) -> Option<Aabb> {
+            Some(Aabb::new(self.min, self.max))
+        }
+
+        fn hash_into(&self, values: &mut Vec<u64>) {
+            ("cuboid", self.min, self.max, self.material).hash_into(values);
+        }
+    }
+
+    // Intersection of a quadric in its local frame, where the axis is +z and
+    // the surface is swept from phi = 0 around to `phi_max`.
+    struct LocalHit {
+        t: f64,
+        normal: Vec3D,
+        u: f64,
+        v: f64,
+    }
+
+    impl LocalHit {
+        fn closer(self, other: Option<LocalHit>) -> LocalHit {
+            match other {
+                Some(other) if other.t < self.t => other,
+                _ => self,
+            }
+        }
+    }
+
+    fn phi_of(p: Point3D) -> f64 {
+        let phi = p.y.atan2(p.x);
+        if phi < 0.0 {
+            phi + 2.0 * PI
+        } else {
+            phi
+        }
+    }
+
+    fn to_local(ray: &Ray, origin: Point3D, axis: &Basis) -> (Point3D, Vec3D) {
+        (
+            axis.to_local(ray.origin - origin),
+            axis.to_local(ray.direction),
+        )
+    }
+
+    // Flat cap of `radius` in the plane `z`, facing `normal_z` along the axis.
+    #[allow(clippy::too_many_arguments)]
+    fn hit_cap(
+        origin: Point3D,
+        direction: Vec3D,
+        z: f64,
+        normal_z: f64,
+        radius: f64,
+        phi_max: f64,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<LocalHit> {
+        if direction.z == 0.0 {
+            return None;
+        }
+        let t = (z - origin.z) / direction.z;
+        if t < t_min || t_max < t {
+            return None;
+        }
+
+        let p = origin + t * direction;
+        let r = (p.x * p.x + p.y * p.y).sqrt();
+        let phi = phi_of(p);
+        if r > radius || phi > phi_max {
+            return None;
+        }
+
+        Some(LocalHit {
+            t,
+            normal: Vec3D::new(0.0, 0.0, normal_z),
+            u: phi / phi_max,
+            v: r / radius,
+        })
+    }
 
-            let sqrt_discriminant = discriminant.sqrt();
-            let mut t = (-half_b - sqrt_discriminant) / a;
+    // Walks the roots of the quadric in ascending order and returns the first
+    // one that lies inside `[t_min, t_max]` and on the clipped surface.
+    fn hit_quadric<F>(
+        roots: Option<(f64, f64)>,
+        t_min: f64,
+        t_max: f64,
+        mut surface: F,
+    ) -> Option<LocalHit>
+    where
+        F: FnMut(f64) -> Option<LocalHit>,
+    {
+        let (t0, t1) = roots?;
+        for t in [t0, t1] {
             if t < t_min || t_max < t {
-                t = (-half_b + sqrt_discriminant) / a;
-                if t < t_min || t_max < t {
+                continue;
+            }
+            if let Some(local_hit) = surface(t) {
+                return Some(local_hit);
+            }
+        }
+        None
+    }
+
+    // Box around the disks of `radius` at both ends of an axis, which holds
+    // any surface of revolution no wider than them
+    fn swept_box(start: Point3D, axis: &Basis, height: f64, radius: f64) -> Aabb {
+        let end = start + height * axis.w;
+        let extent = |w: f64| radius * (1.0 - w * w).max(0.0).sqrt();
+        let r = Vec3D::new(extent(axis.w.x), extent(axis.w.y), extent(axis.w.z));
+        Aabb::new(start - r, start + r).union(&Aabb::new(end - r, end + r))
+    }
+
+    fn to_hit_record(
+        local_hit: LocalHit,
+        axis: &Basis,
+        material: MaterialId,
+        ray: &Ray,
+    ) -> HitRecord {
+        let normal = axis.to_world(local_hit.normal).normalize();
+        HitRecord::new(ray.at(local_hit.t), normal, material, local_hit.t, ray)
+            .with_uv(local_hit.u, local_hit.v)
+    }
+
+    /// Cylinder of `radius` around the segment from `base` to `top`.
+    #[derive(Clone)]
+    pub struct Cylinder {
+        pub base: Point3D,
+        pub axis: Basis,
+        pub radius: f64,
+        pub height: f64,
+        pub phi_max: f64,
+        pub capped: bool,
+        pub material: MaterialId,
+    }
+
+    impl Cylinder {
+        pub fn new(base: Point3D, top: Point3D, radius: f64, material: MaterialId) -> Self {
+            Self {
+                base,
+                axis: Basis::from_w(top - base),
+                radius,
+                height: (top - base).length(),
+                phi_max: 2.0 * PI,
+                capped: false,
+                material,
+            }
+        }
+
+        /// Closes both ends of the cylinder with flat disks.
+        pub fn with_caps(mut self) -> Self {
+            self.capped = true;
+            self
+        }
+
+        /// Sweeps the surface only `phi_max` degrees around the axis.
+        pub fn with_phi_max(mut self, phi_max: f64) -> Self {
+            self.phi_max = phi_max.clamp(0.0, 360.0).to_radians();
+            self
+        }
+    }
+
+    impl Hit for Cylinder {
+        fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+            let (o, d) = to_local(ray, self.base, &self.axis);
+
+            let a = d.x * d.x + d.y * d.y;
+            let b = 2.0 * (o.x * d.x + o.y * d.y);
+            let c = o.x * o.x + o.y * o.y - self.radius.powi(2);
+            let roots = if a == 0.0 {
+                None
+            } else {
+                solve_quadratic(a, b, c)
+            };
+
+            let mut closest = hit_quadric(roots, t_min, t_max, |t| {
+                let p = o + t * d;
+                let phi = phi_of(p);
+                if p.z < 0.0 || self.height < p.z || phi > self.phi_max {
                     return None;
                 }
+                Some(LocalHit {
+                    t,
+                    normal: Vec3D::new(p.x, p.y, 0.0) / self.radius,
+                    u: phi / self.phi_max,
+                    v: p.z / self.height,
+                })
+            });
+
+            if self.capped {
+                let t_max = closest.as_ref().map_or(t_max, |local_hit| local_hit.t);
+                for (z, normal_z) in [(0.0, -1.0), (self.height, 1.0)] {
+                    if let Some(cap) =
+                        hit_cap(o, d, z, normal_z, self.radius, self.phi_max, t_min, t_max)
+                    {
+                        closest = Some(cap.closer(closest));
+                    }
+                }
             }
-            let hit_point = ray.at(t);
-            let normal = (hit_point - self.center) / self.radius;
-            let hit_record = HitRecord::new(hit_point, normal, self.material.clone(), t, ray);
 
-            Some(hit_record)
+            closest.map(|local_hit| to_hit_record(local_hit, &self.axis, self.material, ray))
+        }
+
+        fn bounding_box(&self) -> Option<Aabb> {
+            Some(swept_box(self.base, &self.axis, self.height, self.radius))
+        }
+
+        fn hash_into(&self, values: &mut Vec<u64>) {
+            (
+                "cylinder",
+                self.base,
+                self.axis,
+                self.radius,
+                self.height,
+                self.phi_max,
+                self.capped,
+                self.material,
+            )
+                .hash_into(values);
         }
     }
-}
 
-use hit::{Hit, HitRecord};
-use ray::Ray;
+    /// Cone with a disk of `radius` at `base` narrowing to a point at `apex`.
+    #[derive(Clone)]
+    pub struct Cone {
+        pub base: Point3D,
+        pub axis: Basis,
+        pub radius: f64,
+        pub height: f64,
+        pub phi_max: f64,
+        pub capped: bool,
+        pub material: MaterialId,
+    }
+
+    impl Cone {
+        pub fn new(base: Point3D, apex: Point3D, radius: f64, material: MaterialId) -> Self {
+            Self {
+                base,
+                axis: Basis::from_w(apex - base),
+                radius,
+                height: (apex - base).length(),
+                phi_max: 2.0 * PI,
+                capped: false,
+                material,
+            }
+        }
+
+        /// Closes the base of the cone with a flat disk.
+        pub fn with_caps(mut self) -> Self {
+            self.capped = true;
+            self
+        }
+
+        /// Sweeps the surface only `phi_max` degrees around the axis.
+        pub fn with_phi_max(mut self, phi_max: f64) -> Self {
+            self.phi_max = phi_max.clamp(0.0, 360.0).to_radians();
+            self
+        }
+    }
+
+    impl Hit for Cone {
+        fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+            let (o, d) = to_local(ray, self.base, &self.axis);
+
+            // x^2 + y^2 = k * (h - z)^2
+            let k = (self.radius / self.height).powi(2);
+            let oz = o.z - self.height;
+            let a = d.x * d.x + d.y * d.y - k * d.z * d.z;
+            let b = 2.0 * (o.x * d.x + o.y * d.y - k * d.z * oz);
+            let c = o.x * o.x + o.y * o.y - k * oz * oz;
+
+            let mut closest = hit_quadric(solve_quadratic(a, b, c), t_min, t_max, |t| {
+                let p = o + t * d;
+                let phi = phi_of(p);
+                if p.z < 0.0 || self.height < p.z || phi > self.phi_max {
+                    return None;
+                }
+                Some(LocalHit {
+                    t,
+                    normal: Vec3D::new(p.x, p.y, k * (self.height - p.z)),
+                    u: phi / self.phi_max,
+                    v: p.z / self.height,
+                })
+            });
+
+            if self.capped {
+                let t_max = closest.as_ref().map_or(t_max, |local_hit| local_hit.t);
+                if let Some(cap) = hit_cap(o, d, 0.0, -1.0, self.radius, self.phi_max, t_min, t_max)
+                {
+                    closest = Some(cap.closer(closest));
+                }
+            }
+
+            closest.map(|local_hit| to_hit_record(local_hit, &self.axis, self.material, ray))
+        }
+
+        fn bounding_box(&self) -> Option<Aabb> {
+            Some(swept_box(self.base, &self.axis, self.height, self.radius))
+        }
+
+        fn hash_into(&self, values: &mut Vec<u64>) {
+            (
+                "cone",
+                self.base,
+                self.axis,
+                self.radius,
+                self.height,
+                self.phi_max,
+                self.capped,
+                self.material,
+            )
+                .hash_into(values);
+        }
+    }
+
+    /// Paraboloid opening from `vertex` to a rim of `radius` at `top`.
+    #[derive(Clone)]
+    pub struct Paraboloid {
+        pub vertex: Point3D,
+        pub axis: Basis,
+        pub radius: f64,
+        pub height: f64,
+        pub phi_max: f64,
+        pub capped: bool,
+        pub material: MaterialId,
+    }
+
+    impl Paraboloid {
+        pub fn new(vertex: Point3D, top: Point3D, radius: f64, material: MaterialId) -> Self {
+            Self {
+                vertex,
+                axis: Basis::from_w(top - vertex),
+                radius,
+                height: (top - vertex).length(),
+                phi_max: 2.0 * PI,
+                capped: false,
+                material,
+            }
+        }
 
-pub type World = Vec<Box<dyn Hit>>;
+        /// Closes the rim of the paraboloid with a flat disk.
+        pub fn with_caps(mut self) -> Self {
+            self.capped = true;
+            self
+        }
+
+        /// Sweeps the surface only `phi_max` degrees around the axis.
+        pub fn with_phi_max(mut self, phi_max: f64) -> Self {
+            self.phi_max = phi_max.clamp(0.0, 360.0).to_radians();
+            self
+        }
+    }
+
+    impl Hit for Paraboloid {
+        fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+            let (o, d) = to_local(ray, self.vertex, &self.axis);
 
-impl Hit for World {
-    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let mut temp_rec = None;
-        let mut closest_so_far = t_max;
+            // z = k * (x^2 + y^2)
+            let k = self.height / self.radius.powi(2);
+            let a = k * (d.x * d.x + d.y * d.y);
+            let b = 2.0 * k * (o.x * d.x + o.y * d.y) - d.z;
+            let c = k * (o.x * o.x + o.y * o.y) - o.z;
 
-        for object in self {
-            if let Some(hit_record) = object.hit(ray, t_min, closest_so_far) {
-                closest_so_far = hit_record.t;
-                temp_rec = Some(hit_record);
+            let mut closest = hit_quadric(solve_quadratic(a, b, c), t_min, t_max, |t| {
+                let p = o + t * d;
+                let phi = phi_of(p);
+                if self.height < p.z || phi > self.phi_max {
+                    return None;
+                }
+                Some(LocalHit {
+                    t,
+                    normal: Vec3D::new(2.0 * k * p.x, 2.0 * k * p.y, -1.0),
+                    u: phi / self.phi_max,
+                    v: p.z / self.height,
+                })
+            });
+
+            if self.capped {
+                let t_max = closest.as_ref().map_or(t_max, |local_hit| local_hit.t);
+                if let Some(cap) = hit_cap(
+                    o,
+                    d,
+                    self.height,
+                    1.0,
+                    self.radius,
+                    self.phi_max,
+                    t_min,
+                    t_max,
+                ) {
+                    closest = Some(cap.closer(closest));
+                }
             }
+
+            closest.map(|local_hit| to_hit_record(local_hit, &self.axis, self.material, ray))
         }
 
-        temp_rec
+        fn bounding_box(&self) -> Option<Aabb> {
+            Some(swept_box(self.vertex, &self.axis, self.height, self.radius))
+        }
+
+        fn hash_into(&self, values: &mut Vec<u64>) {
+            (
+                "paraboloid",
+                self.vertex,
+                self.axis,
+                self.radius,
+                self.height,
+                self.phi_max,
+                self.capped,
+                self.material,
+            )
+                .hash_into(values);
+        }
+    }
+
+    /// Ring of `minor_radius` swept at `major_radius` around `axis` through `center`.
+    #[derive(Clone)]
+    pub struct Torus {
+        pub center: Point3D,
+        pub axis: Basis,
+        pub major_radius: f64,
+        pub minor_radius: f64,
+        pub material: MaterialId,
+    }
+
+    impl Torus {
+        pub fn new(
+            center: Point3D,
+            axis: Vec3D,
+            major_radius: f64,
+            minor_radius: f64,
+            material: MaterialId,
+        ) -> Self {
+            Self {
+                center,
+                axis: Basis::from_w(axis),
+                major_radius,
+                minor_radius,
+                material,
+            }
+        }
+    }
+
+    impl Hit for Torus {
+        fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+            let (o, d) = to_local(ray, self.center, &self.axis);
+            let (major, minor) = (self.major_radius, self.minor_radius);
+
+            // Solve in terms of a unit direction starting from the bounding
+            // sphere, which keeps the quartic coefficients small
+            let scale = d.length();
+            let d = d / scale;
+            let (t_near, _) =
+                solve_quadratic(1.0, 2.0 * o.dot(d), o.dot(o) - (major + minor).powi(2))?;
+            let o = o + t_near * d;
+
+            // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + y^2)
+            let f = o.dot(d);
+            let e = o.dot(o) + major * major - minor * minor;
+            let four_major_sq = 4.0 * major * major;
+            let roots = solve_quartic(
+                1.0,
+                4.0 * f,
+                4.0 * f * f + 2.0 * e - four_major_sq * (d.x * d.x + d.y * d.y),
+                4.0 * f * e - 2.0 * four_major_sq * (o.x * d.x + o.y * d.y),
+                e * e - four_major_sq * (o.x * o.x + o.y * o.y),
+            );
+
+            let (s, t) = roots
+                .into_iter()
+                .map(|s| (s, (t_near + s) / scale))
+                .find(|&(_, t)| t_min <= t && t <= t_max)?;
+
+            let p = o + s * d;
+            let ring = (p.x * p.x + p.y * p.y).sqrt();
+            let nearest_on_ring = if ring == 0.0 {
+                Vec3D::new(major, 0.0, 0.0)
+            } else {
+                Vec3D::new(p.x, p.y, 0.0) * (major / ring)
+            };
+            let normal = self
+                .axis
+                .to_world((p - nearest_on_ring) / minor)
+                .normalize();
+
+            let mut theta = p.z.atan2(ring - major);
+            if theta < 0.0 {
+                theta += 2.0 * PI;
+            }
+            let u = phi_of(p) / (2.0 * PI);
+            let v = theta / (2.0 * PI);
+
+            let hit_record = HitRecord::new(ray.at(t), normal, self.material, t, ray).with_uv(u, v);
+            Some(hit_record)
+        }
+
+        fn bounding_box(&self) -> Option<Aabb> {
+            let r = self.major_radius + self.minor_radius;
+            Some(Aabb::new(
+                self.center - Vec3D::new(r, r, r),
+                self.center + Vec3D::new(r, r, r),
+            ))
+        }
+
+        fn hash_into(&self, values: &mut Vec<u64>) {
+            (
+                "torus",
+                self.center,
+                self.axis,
+                self.major_radius,
+                self.minor_radius,
+                self.material,
+            )
+                .hash_into(values);
+        }
+    }
+
+    /// Places a shared object in the world through an affine transform.
+    ///
+    /// The geometry is held behind an `Arc`, so a single heavy object can be
+    /// instanced many times without being copied.
+    #[derive(Clone)]
+    pub struct Instance {
+        pub object: Arc<dyn Hit>,
+        pub transform: Transform,
+    }
+
+    impl Instance {
+        pub fn new(object: Arc<dyn Hit>, transform: Transform) -> Self {
+            Self { object, transform }
+        }
+    }
+
+    impl Hit for Instance {
+        fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+            hit_transformed(self.object.as_ref(), &self.transform, ray, t_min, t_max)
+        }
+
+        fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<f64> {
+            let ray = to_object_space(&self.transform, ray);
+            self.object.intersect(&ray, t_min, t_max)
+        }
+
+        fn spans(&self, ray: &Ray) -> Vec<Span> {
+            spans_transformed(self.object.as_ref(), &self.transform, ray)
+        }
+
+        fn bounding_box(&self) -> Option<Aabb> {
+            Some(self.object.bounding_box()?.transform(&self.transform))
+        }
+
+        fn hash_into(&self, values: &mut Vec<u64>) {
+            "instance".hash_into(values);
+            self.object.hash_into(values);
+            self.transform.hash_into(values);
+        }
+    }
+
+    fn hit_transformed(
+        object: &dyn Hit,
+        transform: &Transform,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<HitRecord> {
+        let hit_record = object.hit(&to_object_space(transform, ray), t_min, t_max)?;
+        Some(to_world_space(transform, hit_record))
+    }
+
+    fn spans_transformed(object: &dyn Hit, transform: &Transform, ray: &Ray) -> Vec<Span> {
+        object
+            .spans(&to_object_space(transform, ray))
+            .into_iter()
+            .map(|span| Span {
+                enter: to_world_space(transform, span.enter),
+                exit: to_world_space(transform, span.exit),
+            })
+            .collect()
+    }
+
+    fn to_object_space(transform: &Transform, ray: &Ray) -> Ray {
+        // The object space direction is not renormalized, so `t` means the
+        // same thing on both sides of the transform
+        let to_object = transform.inverse();
+        Ray::new(
+            to_object.point(ray.origin),
+            to_object.vector(ray.direction),
+            ray.time,
+        )
+    }
+
+    fn to_world_space(transform: &Transform, mut hit_record: HitRecord) -> HitRecord {
+        hit_record.hit_point = transform.point(hit_record.hit_point);
+        hit_record.normal = transform.normal(hit_record.normal).normalize();
+        hit_record.tangent = hit_record
+            .tangent
+            .map(|tangent| transform.vector(tangent).normalize());
+        hit_record
+    }
+
+    /// Instance whose transform follows keyframes over the camera shutter.
+    #[derive(Clone)]
+    pub struct AnimatedInstance {
+        pub object: Arc<dyn Hit>,
+        pub animation: AnimatedTransform,
+    }
+
+    impl AnimatedInstance {
+        pub fn new(object: Arc<dyn Hit>, animation: AnimatedTransform) -> Self {
+            Self { object, animation }
+        }
+    }
+
+    impl Hit for AnimatedInstance {
+        fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+            let transform = self.animation.at(ray.time);
+            hit_transformed(self.object.as_ref(), &transform, ray, t_min, t_max)
+        }
+
+        fn spans(&self, ray: &Ray) -> Vec<Span> {
+            let transform = self.animation.at(ray.time);
+            spans_transformed(self.object.as_ref(), &transform, ray)
+        }
+
+        fn hash_into(&self, values: &mut Vec<u64>) {
+            "animated instance".hash_into(values);
+            self.object.hash_into(values);
+            self.animation.hash_into(values);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        hit::Hit,
+        material::MaterialId,
+        object::{Cone, Cylinder, Instance, MovingSphere, Paraboloid, Sphere, Torus},
+        ray::Ray,
+        vec::{Point3D, Transform, Vec3D},
+    };
+
+    fn material() -> MaterialId {
+        MaterialId(0)
+    }
+
+    #[test]
+    fn test_cylinder_side_and_caps() {
+        let cylinder = Cylinder::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(0.0, 2.0, 0.0),
+            1.0,
+            material(),
+        );
+
+        let side = Ray::new(Point3D::new(-5.0, 0.5, 0.0), Vec3D::new(1.0, 0.0, 0.0), 0.0);
+        let hit = cylinder.hit(&side, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 4.0).abs() < 1.0e-9);
+        assert!((hit.normal - Vec3D::new(-1.0, 0.0, 0.0)).is_near_zero());
+        assert!((hit.v - 0.25).abs() < 1.0e-9);
+
+        let down = Ray::new(Point3D::new(0.0, 5.0, 0.0), Vec3D::new(0.0, -1.0, 0.0), 0.0);
+        assert!(cylinder.hit(&down, 0.001, f64::INFINITY).is_none());
+
+        let capped = cylinder.with_caps();
+        let hit = capped.hit(&down, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 3.0).abs() < 1.0e-9);
+        assert!((hit.normal - Vec3D::new(0.0, 1.0, 0.0)).is_near_zero());
+    }
+
+    #[test]
+    fn test_cylinder_partial_sweep() {
+        let half = Cylinder::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(0.0, 0.0, 1.0),
+            1.0,
+            material(),
+        )
+        .with_phi_max(180.0);
+
+        // The swept half lies at y >= 0, so a ray along -y only meets the far wall
+        let ray = Ray::new(Point3D::new(0.0, -5.0, 0.5), Vec3D::new(0.0, 1.0, 0.0), 0.0);
+        let hit = half.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 6.0).abs() < 1.0e-9);
+        assert!(!hit.front_face);
+    }
+
+    #[test]
+    fn test_cone_and_paraboloid_normals() {
+        let cone = Cone::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(0.0, 1.0, 0.0),
+            1.0,
+            material(),
+        );
+        let ray = Ray::new(Point3D::new(-5.0, 0.5, 0.0), Vec3D::new(1.0, 0.0, 0.0), 0.0);
+        let hit = cone.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 4.5).abs() < 1.0e-9);
+        let expected = Vec3D::new(-1.0, 1.0, 0.0).normalize();
+        assert!((hit.normal - expected).length() < 1.0e-9);
+
+        let paraboloid = Paraboloid::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(0.0, 1.0, 0.0),
+            1.0,
+            material(),
+        );
+        let ray = Ray::new(Point3D::new(0.0, -5.0, 0.0), Vec3D::new(0.0, 1.0, 0.0), 0.0);
+        let hit = paraboloid.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 5.0).abs() < 1.0e-9);
+        assert!((hit.normal - Vec3D::new(0.0, -1.0, 0.0)).is_near_zero());
+    }
+
+    #[test]
+    fn test_torus_hit() {
+        let torus = Torus::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Vec3D::new(0.0, 1.0, 0.0),
+            2.0,
+            0.5,
+            material(),
+        );
+
+        // Along the axis the ray passes through the hole
+        let through_hole = Ray::new(Point3D::new(0.0, 5.0, 0.0), Vec3D::new(0.0, -1.0, 0.0), 0.0);
+        assert!(torus.hit(&through_hole, 0.001, f64::INFINITY).is_none());
+
+        let ray = Ray::new(
+            Point3D::new(-10.0, 0.0, 0.0),
+            Vec3D::new(2.0, 0.0, 0.0),
+            0.0,
+        );
+        let hit = torus.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 3.75).abs() < 1.0e-9);
+        assert!((hit.normal - Vec3D::new(-1.0, 0.0, 0.0)).is_near_zero());
+
+        // From inside the tube the next hit is the inner wall of the same side
+        let inside = Ray::new(Point3D::new(-2.0, 0.0, 0.0), Vec3D::new(1.0, 0.0, 0.0), 0.0);
+        let hit = inside.at(torus.hit(&inside, 0.001, f64::INFINITY).unwrap().t);
+        assert!((hit - Point3D::new(-1.5, 0.0, 0.0)).length() < 1.0e-9);
+    }
+
+    #[test]
+    fn test_instance_shares_transformed_geometry() {
+        let sphere: Arc<dyn Hit> =
+            Arc::new(Sphere::new(Point3D::new(0.0, 0.0, 0.0), 1.0, material()));
+        let ellipsoid = Instance::new(
+            sphere.clone(),
+            Transform::translate(Vec3D::new(0.0, 0.0, -5.0))
+                * Transform::scale(Vec3D::new(2.0, 1.0, 1.0)),
+        );
+
+        let ray = Ray::new(
+            Point3D::new(-10.0, 0.0, -5.0),
+            Vec3D::new(1.0, 0.0, 0.0),
+            0.0,
+        );
+        let hit = ellipsoid.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 8.0).abs() < 1.0e-9);
+        assert!((hit.hit_point - Point3D::new(-2.0, 0.0, -5.0)).length() < 1.0e-9);
+
+        // Off the long axis the normal is no longer radial
+        let ray = Ray::new(
+            Point3D::new(1.0, 10.0, -5.0),
+            Vec3D::new(0.0, -1.0, 0.0),
+            0.0,
+        );
+        let hit = ellipsoid.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        let expected = Vec3D::new(1.0 / 4.0, 3.0f64.sqrt() / 2.0, 0.0).normalize();
+        assert!((hit.normal - expected).length() < 1.0e-9);
+
+        assert_eq!(Arc::strong_count(&sphere), 2);
+    }
+
+    #[test]
+    fn test_moving_sphere_follows_ray_time() {
+        let sphere = MovingSphere::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(0.0, 2.0, 0.0),
+            0.0,
+            1.0,
+            0.5,
+            material(),
+        );
+
+        let at = |time| {
+            Ray::new(
+                Point3D::new(0.0, 1.0, 5.0),
+                Vec3D::new(0.0, 0.0, -1.0),
+                time,
+            )
+        };
+        assert!(sphere.hit(&at(0.0), 0.001, f64::INFINITY).is_none());
+        let hit = sphere.hit(&at(0.5), 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 4.5).abs() < 1.0e-9);
+
+        // Past the end of its motion the sphere rests where it stopped
+        assert_eq!(sphere.center(3.0), Point3D::new(0.0, 2.0, 0.0));
+        assert_eq!(sphere.center(-1.0), Point3D::new(0.0, 0.0, 0.0));
     }
 }
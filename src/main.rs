@@ -1,16 +1,19 @@
-use std::sync::Arc;
+use std::{io::Write, sync::Arc, time::Duration};
 
-use rand::Rng;
-use rayon::iter::IntoParallelIterator;
-use rayon::iter::ParallelIterator;
+use rand::{Rng, SeedableRng};
 
 use rust_ray_tracer::{
-    camera::Camera,
+    camera::{FieldOfView, PerspectiveCamera},
     color::Color,
+    film::Filter,
     material::{Dielectric, Lambertian, Metal},
     object::Sphere,
-    vec::{Point2D, Point3D, Vec3D},
-    World,
+    progress::Progress,
+    random::SampleRng,
+    render::{Adaptive, Progressive, Renderer},
+    sampler::SobolSampler,
+    scene::{Scene, SceneBuilder},
+    vec::{Point3D, Vec3D},
 };
 
 const ASPECT_RATIO: f64 = 16.0 / 9.0;
@@ -18,142 +21,174 @@ const WIDTH: usize = 256;
 const HEIGHT: usize = ((WIDTH as f64) / ASPECT_RATIO) as usize;
 const SAMPLES_PER_PIXEL: usize = 50;
 const MAX_RAY_BOUNCE_DEPTH: usize = 50;
-const ANTI_ALIAS: bool = true;
+// The same seed renders the same image
+const SEED: u64 = 0;
+// Pixels stop being sampled once their relative noise drops below this,
+// after at least `MIN_SAMPLES_PER_PIXEL`; `None` samples all of them fully
+const NOISE_THRESHOLD: Option<f64> = Some(0.02);
+const MIN_SAMPLES_PER_PIXEL: usize = 16;
+const FILTER: Filter = Filter::Mitchell {
+    radius: 2.0,
+    b: 1.0 / 3.0,
+    c: 1.0 / 3.0,
+};
+// Where to write a map of the samples each pixel took
+const HEATMAP_PATH: Option<&str> = None;
+// Passing this reports progress as one JSON object per line on stderr,
+// instead of drawing a bar there
+const JSON_PROGRESS_FLAG: &str = "--progress=json";
+const PROGRESS_BAR_WIDTH: usize = 40;
+// Renders progressively for this long instead of to `SAMPLES_PER_PIXEL`,
+// writing the image so far to `SNAPSHOT_PATH` every `SNAPSHOT_INTERVAL`
+const TIME_BUDGET: Option<Duration> = None;
+const SNAPSHOT_PATH: &str = "snapshot.ppm";
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(30);
+// Progressive renders also save their state here, and passing
+// `RESUME_FLAG` carries on from it
+const CHECKPOINT_PATH: &str = "render.checkpoint";
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(300);
+const RESUME_FLAG: &str = "--resume";
 
 trait RayTraceable {
-    fn trace_to_ppm_with(&self, camera: Camera, world: World);
+    fn trace_to_ppm_with(&self, scene: Scene);
 }
 
-type Grid = [[u8; WIDTH]; HEIGHT];
-
 struct Image {
-    pub inner: Grid,
     pub width: usize,
     pub height: usize,
-    pub aspect_ratio: f64,
 }
 
 impl Image {
     pub fn new(width: usize, height: usize) -> Self {
-        Self {
-            inner: [[0u8; WIDTH]; HEIGHT],
-            width,
-            height,
-            aspect_ratio: (width as f64) / (height as f64),
-        }
+        Self { width, height }
     }
+}
 
-    /* fn fill_center_circle(&mut self, radius: f64) {
-        let origin = Point2D {
-            x: (self.width / 2) as f64,
-            y: (self.height / 2) as f64,
-        };
-
-        let calc_distance =
-            |x: f64, y: f64| -> f64 { (x - origin.x).powf(2.0) + (y - origin.y).powf(2.0) };
-
-        for y in 0..HEIGHT {
-            for x in 0..WIDTH {
-                if calc_distance(x as f64, y as f64) < radius.powf(2.0) {
-                    self.inner[y][x] = 1;
-                }
-            }
-        }
-    } */
-
-    fn write_ppm_stdout(&self, fg: Color, bg: Color) {
+impl RayTraceable for Image {
+    fn trace_to_ppm_with(&self, scene: Scene) {
         println!("P3");
         println!("{} {}", self.width, self.height);
         println!("255");
 
-        for y in 0..HEIGHT {
-            for x in 0..WIDTH {
-                if self.inner[y][x] == 1 {
-                    println!("{}", fg);
-                    continue;
-                }
-                println!("{}", bg);
-            }
+        let mut renderer = Renderer::new(self.width, self.height, SAMPLES_PER_PIXEL)
+            .with_max_depth(MAX_RAY_BOUNCE_DEPTH)
+            .with_filter(FILTER);
+        renderer = if std::env::args().any(|arg| arg == JSON_PROGRESS_FLAG) {
+            renderer.with_progress(|progress| eprintln!("{}", progress_json(progress)))
+        } else {
+            renderer.with_progress(|progress| {
+                let _ = write!(std::io::stderr().lock(), "\r{}", progress_bar(progress));
+            })
+        };
+        if let Some(threshold) = NOISE_THRESHOLD {
+            renderer = renderer.with_adaptive(Adaptive::new(
+                MIN_SAMPLES_PER_PIXEL,
+                SAMPLES_PER_PIXEL,
+                threshold,
+            ));
         }
-    }
-
-    /* fn fill_intersect(&mut self) {
-        let ray_intersect_sphere = |_x: f64, _y: f64| -> bool { false };
-
-        for y in 0..HEIGHT {
-            for x in 0..WIDTH {
-                if ray_intersect_sphere(x as f64, y as f64) {
-                    self.inner[y][x] = 1;
+        let sampler = SobolSampler::new(SAMPLES_PER_PIXEL, SEED);
+        let frame = match TIME_BUDGET {
+            None => renderer.render(&scene, &sampler),
+            Some(budget) => {
+                let mut progressive = Progressive::for_time(budget)
+                    .with_snapshots(SNAPSHOT_PATH, SNAPSHOT_INTERVAL)
+                    .with_checkpoints(CHECKPOINT_PATH, CHECKPOINT_INTERVAL);
+                if std::env::args().any(|arg| arg == RESUME_FLAG) {
+                    progressive = progressive.with_resume(CHECKPOINT_PATH);
+                }
+                match renderer.render_progressive(&scene, &sampler, &progressive) {
+                    Ok(frame) => frame,
+                    Err(error) => {
+                        eprintln!("progressive render failed: {}", error);
+                        return;
+                    }
                 }
             }
-        }
-    } */
-}
-
-impl RayTraceable for Image {
-    fn trace_to_ppm_with(&self, camera: Camera, world: World) {
-        let get_uv = |x, y, random_u, random_v| -> (f64, f64) {
-            let u: f64 = ((x as f64) + random_u) / ((self.width - 1) as f64);
-            let v: f64 = ((y as f64) + random_v) / ((self.height - 1) as f64);
-            (u, v)
         };
+        if std::env::args().all(|arg| arg != JSON_PROGRESS_FLAG) {
+            eprintln!();
+        }
 
-        println!("P3");
-        println!("{} {}", self.width, self.height);
-        println!("255");
-
-        /* let mut rng = rand::thread_rng();
         for y in (0..self.height).rev() {
             for x in 0..self.width {
-                let mut sum_color: Vec3D = Color::new(0.0, 0.0, 0.0).to_vec3d();
-                for _ in 0..SAMPLES_PER_PIXEL {
-                    let (u, v) = get_uv(x, y, rng.gen::<f64>(), rng.gen::<f64>());
-                    let ray = camera.get_ray(u, v);
-                    let color = ray.color(&world, MAX_RAY_BOUNCE_DEPTH).to_vec3d();
-                    sum_color = sum_color + color;
-                }
                 println!(
                     "{}",
-                    Color::RGB(sum_color.format_color(SAMPLES_PER_PIXEL as u64))
+                    Color::RGB(frame.color(x, y).to_vec3d().format_color(1))
                 );
             }
-        } */
-
-        for y in (0..self.height).rev() {
-            eprintln!("{}", (self.height - y) / self.height * 100);
-            let scanlines: Vec<Vec3D> = (0..self.width)
-                .into_par_iter()
-                .map(|x| {
-                    let mut sum_color: Vec3D = Color::new(0.0, 0.0, 0.0).to_vec3d();
-                    for _ in 0..SAMPLES_PER_PIXEL {
-                        let mut rng = rand::thread_rng();
-                        let (u, v) = get_uv(x, y, rng.gen::<f64>(), rng.gen::<f64>());
-                        let ray = camera.get_ray(u, v);
-                        let color = ray.color(&world, MAX_RAY_BOUNCE_DEPTH).to_vec3d();
-                        sum_color = sum_color + color;
-                    }
-                    sum_color
-                })
-                .collect();
+        }
 
-            for line in scanlines {
-                println!(
-                    "{}",
-                    Color::RGB(line.format_color(SAMPLES_PER_PIXEL as u64))
-                );
+        if let Some(path) = HEATMAP_PATH {
+            let heatmap = frame.heatmap();
+            let mut ppm = format!("P3\n{} {}\n255\n", self.width, self.height);
+            for y in (0..self.height).rev() {
+                for x in 0..self.width {
+                    let color = heatmap[y * self.width + x].to_vec3d().format_color(1);
+                    ppm += &format!("{}\n", Color::RGB(color));
+                }
+            }
+            if let Err(error) = std::fs::write(path, ppm) {
+                eprintln!("couldn't write {}: {}", path, error);
             }
         }
     }
 }
 
-fn random_world() -> World {
-    let mut rng = rand::thread_rng();
-    let mut world = World::new();
+fn progress_bar(progress: &Progress) -> String {
+    let filled = (progress.fraction() * PROGRESS_BAR_WIDTH as f64) as usize;
+    let eta = progress
+        .eta()
+        .map_or("?".to_string(), |eta| format!("{}s", eta.as_secs()));
+    // Tiles aren't known ahead when rendering for a time budget
+    let tiles = if progress.tiles > 0 {
+        format!("{}/{}", progress.tiles_done, progress.tiles)
+    } else {
+        progress.tiles_done.to_string()
+    };
+    format!(
+        "[{}{}] {:3.0}% {} tiles {:.2} Mrays/s {}s elapsed ETA {}  ",
+        "#".repeat(filled),
+        "-".repeat(PROGRESS_BAR_WIDTH - filled),
+        progress.fraction() * 100.0,
+        tiles,
+        progress.rays_per_second() / 1.0e6,
+        progress.elapsed.as_secs(),
+        eta,
+    )
+}
+
+fn progress_json(progress: &Progress) -> String {
+    let eta = progress.eta().map_or("null".to_string(), |eta| {
+        format!("{:.3}", eta.as_secs_f64())
+    });
+    let budget = progress.budget.map_or("null".to_string(), |budget| {
+        format!("{:.3}", budget.as_secs_f64())
+    });
+    format!(
+        "{{\"tiles_done\":{},\"tiles\":{},\"pixels_done\":{},\"pixels\":{},\"samples\":{},\"rays\":{},\"rays_per_second\":{:.0},\"elapsed\":{:.3},\"eta\":{},\"budget\":{},\"fraction\":{:.6}}}",
+        progress.tiles_done,
+        progress.tiles,
+        progress.pixels_done,
+        progress.pixels,
+        progress.samples,
+        progress.rays,
+        progress.rays_per_second(),
+        progress.elapsed.as_secs_f64(),
+        eta,
+        budget,
+        progress.fraction(),
+    )
+}
+
+fn random_world() -> SceneBuilder {
+    let mut rng = SampleRng::seed_from_u64(SEED);
+    let mut world = Scene::builder();
 
-    let ground_mat = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+    let ground_mat = world.add_material(Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
     let ground_sphere = Sphere::new(Point3D::new(0.0, -1000.0, 0.0), 1000.0, ground_mat);
 
-    world.push(Box::new(ground_sphere));
+    world.add(ground_sphere);
 
     for a in -11..=11 {
         for b in -11..=11 {
@@ -166,117 +201,48 @@ fn random_world() -> World {
 
             if choose_mat < 0.8 {
                 // Diffuse
-                let albedo = Color::RGB(Vec3D::random(0.0..1.0) * Vec3D::random(0.0..1.0));
-                let sphere_mat = Arc::new(Lambertian::new(albedo));
+                let albedo = Color::RGB(
+                    Vec3D::random(0.0..1.0, &mut rng) * Vec3D::random(0.0..1.0, &mut rng),
+                );
+                let sphere_mat = world.add_material(Arc::new(Lambertian::new(albedo)));
                 let sphere = Sphere::new(center, 0.2, sphere_mat);
 
-                world.push(Box::new(sphere));
+                world.add(sphere);
             } else if choose_mat < 0.95 {
                 // Metal
-                let albedo = Color::RGB(Vec3D::random(0.4..1.0));
+                let albedo = Color::RGB(Vec3D::random(0.4..1.0, &mut rng));
                 let fuzz = rng.gen_range(0.0..0.5);
-                let sphere_mat = Arc::new(Metal::new(albedo, fuzz));
+                let sphere_mat = world.add_material(Arc::new(Metal::new(albedo, fuzz)));
                 let sphere = Sphere::new(center, 0.2, sphere_mat);
 
-                world.push(Box::new(sphere));
+                world.add(sphere);
             } else {
                 // Glass
-                let sphere_mat = Arc::new(Dielectric::new(1.5));
+                let sphere_mat = world.add_material(Arc::new(Dielectric::new(1.5)));
                 let sphere = Sphere::new(center, 0.2, sphere_mat);
 
-                world.push(Box::new(sphere));
+                world.add(sphere);
             }
         }
     }
 
-    let mat1 = Arc::new(Dielectric::new(1.5));
-    let mat2 = Arc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1)));
-    let mat3 = Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0));
+    let mat1 = world.add_material(Arc::new(Dielectric::new(1.5)));
+    let mat2 = world.add_material(Arc::new(Lambertian::new(Color::new(0.4, 0.2, 0.1))));
+    let mat3 = world.add_material(Arc::new(Metal::new(Color::new(0.7, 0.6, 0.5), 0.0)));
 
     let sphere1 = Sphere::new(Point3D::new(0.0, 1.0, 0.0), 1.0, mat1);
     let sphere2 = Sphere::new(Point3D::new(-4.0, 1.0, 0.0), 1.0, mat2);
     let sphere3 = Sphere::new(Point3D::new(4.0, 1.0, 0.0), 1.0, mat3);
 
-    world.push(Box::new(sphere1));
-    world.push(Box::new(sphere2));
-    world.push(Box::new(sphere3));
+    world.add(sphere1);
+    world.add(sphere2);
+    world.add(sphere3);
 
     world
 }
 
-fn a() {
-    let material_ground = Arc::new(Lambertian::new(Color::new(0.8, 0.8, 0.0)));
-    let material_center = Arc::new(Lambertian::new(Color::new(0.1, 0.2, 0.5)));
-    let material_left = Arc::new(Dielectric::new(1.5));
-    let material_left_inner = Arc::new(Dielectric::new(1.1));
-    let material_right = Arc::new(Metal::new(Color::new(0.8, 0.6, 0.2), 0.0));
-
-    let mut world = World::new();
-    world.push(Box::new(Sphere::new(
-        Point3D::new(0.0, -100.5, -1.0),
-        100.0,
-        material_ground,
-    )));
-    world.push(Box::new(Sphere::new(
-        Point3D::new(0.0, 0.0, -1.0),
-        0.5,
-        material_center,
-    )));
-    /* world.push(Box::new(Sphere::new(
-        Point3D::new(-1.0, 0.0, -1.0),
-        0.5,
-        material_left,
-    ))); */
-    world.push(Box::new(Sphere::new(
-        Point3D::new(-1.0, 0.0, -1.0),
-        -0.45,
-        material_left_inner,
-    )));
-    world.push(Box::new(Sphere::new(
-        Point3D::new(1.0, 0.0, -1.0),
-        0.5,
-        material_right,
-    )));
-
-    let image = Image::new(WIDTH, HEIGHT);
-
-    let lookfrom = Point3D::new(3.0, 3.0, 2.0);
-    let lookat = Point3D::new(0.0, 0.0, -1.0);
-    let dist_to_focus = (lookfrom - lookat).length();
-    let aperture = 1.0;
-    let camera = Camera::new(
-        lookfrom,
-        lookat,
-        Vec3D::new(0.0, 1.0, 0.0),
-        20.0,
-        1.0,
-        ASPECT_RATIO,
-        aperture,
-        dist_to_focus,
-    );
-    image.trace_to_ppm_with(camera, world);
-}
-
-fn b() {
-    let r: f64 = (std::f64::consts::PI / 4.0).cos();
-    let mut world = World::new();
-
-    let mat_left = Arc::new(Lambertian::new(Color::new(0.0, 0.0, 1.0)));
-    let mat_right = Arc::new(Lambertian::new(Color::new(1.0, 0.0, 0.0)));
-
-    let sphere_left = Sphere::new(Point3D::new(-r, 0.0, -1.0), r, mat_left);
-    let sphere_right = Sphere::new(Point3D::new(r, 0.0, -1.0), r, mat_right);
-
-    world.push(Box::new(sphere_left));
-    world.push(Box::new(sphere_right));
-
-    /* let image = Image::new(WIDTH, HEIGHT);
-    let camera = Camera::new(90.0, 1.0, ASPECT_RATIO);
-    image.trace_to_ppm_with(camera, world); */
-}
-
 fn c() {
-    let world = random_world();
+    let mut world = random_world();
 
     let lookfrom = Point3D::new(13.0, 2.0, 3.0);
     let lookat = Point3D::new(0.0, 0.0, 0.0);
@@ -285,21 +251,19 @@ fn c() {
     let aperture = 0.1;
 
     let image = Image::new(WIDTH, HEIGHT);
-    let camera = Camera::new(
+    let camera = PerspectiveCamera::new(
         lookfrom,
         lookat,
         view_up,
-        20.0,
-        1.0,
+        FieldOfView::Vertical(20.0),
         ASPECT_RATIO,
         aperture,
         dist_to_focus,
     );
-    image.trace_to_ppm_with(camera, world);
+    world.set_camera(camera);
+    image.trace_to_ppm_with(world.build());
 }
 
 fn main() {
-    // a();
-    // b();
     c();
 }
@@ -1,31 +1,107 @@
-use rand::Rng;
+use std::sync::Arc;
 
-use crate::{color::Color, hit::HitRecord, ray::Ray, vec::Vec3D};
+use crate::{
+    color::Color, content::HashInto, hit::HitRecord, ray::Ray, sampler::Sampler, texture::Texture,
+    vec::Vec3D,
+};
+
+/// Index of a material in a scene's material table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MaterialId(pub usize);
+
+impl HashInto for MaterialId {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        self.0.hash_into(values);
+    }
+}
+
+/// Uniform numbers for one bounce. They are drawn before the material is
+/// known so that every bounce uses the same sampler dimensions.
+#[derive(Debug, Clone, Copy)]
+pub struct ScatterSample {
+    /// Picks between lobes, such as reflecting or refracting.
+    pub choice: f64,
+    /// Direction on the unit sphere.
+    pub direction: (f64, f64),
+    /// Depth inside the unit sphere, for fuzzy reflections.
+    pub radius: f64,
+}
+
+impl ScatterSample {
+    pub fn draw(sampler: &mut dyn Sampler) -> Self {
+        Self {
+            choice: sampler.get_1d(),
+            direction: sampler.get_2d(),
+            radius: sampler.get_1d(),
+        }
+    }
+
+    pub fn in_unit_sphere(&self) -> Vec3D {
+        Vec3D::in_unit_sphere(self.direction, self.radius)
+    }
+}
 
 pub trait Scatter: Send + Sync {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)>;
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sample: &ScatterSample,
+    ) -> Option<(Color, Ray)>;
+
+    /// Light given off by the surface itself, added on top of whatever it scatters.
+    fn emitted(&self, _hit_record: &HitRecord) -> Color {
+        Color::Black
+    }
+
+    /// Adds everything that decides how the material looks to `values`,
+    /// see `Hit::hash_into`.
+    fn hash_into(&self, values: &mut Vec<u64>);
+}
+
+fn hash_texture(texture: Option<&Arc<dyn Texture>>, values: &mut Vec<u64>) {
+    texture.is_some().hash_into(values);
+    if let Some(texture) = texture {
+        texture.hash_into(values);
+    }
 }
 
 pub struct Lambertian {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
     pub fn new(color: Color) -> Self {
-        Self { albedo: color }
+        Self {
+            albedo: Arc::new(color),
+        }
+    }
+
+    pub fn textured(albedo: Arc<dyn Texture>) -> Self {
+        Self { albedo }
     }
 }
 
 impl Scatter for Lambertian {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
-        let mut scatter_direction = hit_record.normal + Vec3D::random_in_unit_sphere().normalize();
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sample: &ScatterSample,
+    ) -> Option<(Color, Ray)> {
+        let mut scatter_direction = hit_record.normal + Vec3D::on_unit_sphere(sample.direction);
         if scatter_direction.is_near_zero() {
             // Catch degenerate scatter direction
             scatter_direction = hit_record.normal;
         }
-        let scattered_ray = Ray::new(hit_record.hit_point, scatter_direction);
+        let scattered_ray = Ray::new(hit_record.hit_point, scatter_direction, ray_in.time);
 
-        Some((self.albedo, scattered_ray))
+        Some((self.albedo.value(hit_record), scattered_ray))
+    }
+
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        "lambertian".hash_into(values);
+        self.albedo.hash_into(values);
     }
 }
 
@@ -40,16 +116,26 @@ impl Hemisphere {
 }
 
 impl Scatter for Hemisphere {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
-        let scatter_direction =
-            hit_record.hit_point + Vec3D::random_in_hemisphere(hit_record.normal);
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sample: &ScatterSample,
+    ) -> Option<(Color, Ray)> {
+        let scatter_direction = hit_record.hit_point
+            + Vec3D::in_hemisphere(hit_record.normal, sample.direction, sample.radius);
         let scattered_ray = Ray::new(
             hit_record.hit_point,
             scatter_direction - hit_record.hit_point,
+            ray_in.time,
         );
 
         Some((self.albedo, scattered_ray))
     }
+
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        ("hemisphere", self.albedo).hash_into(values);
+    }
 }
 
 pub struct Metal {
@@ -67,11 +153,17 @@ impl Metal {
 }
 
 impl Scatter for Metal {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sample: &ScatterSample,
+    ) -> Option<(Color, Ray)> {
         let reflected_vector = ray_in.direction.reflect(hit_record.normal).normalize();
         let scattered_ray = Ray::new(
             hit_record.hit_point,
-            reflected_vector + self.fuzz * Vec3D::random_in_unit_sphere(),
+            reflected_vector + self.fuzz * sample.in_unit_sphere(),
+            ray_in.time,
         );
 
         if scattered_ray.direction.dot(hit_record.normal) > 0.0 {
@@ -80,6 +172,10 @@ impl Scatter for Metal {
             None
         }
     }
+
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        ("metal", self.albedo, self.fuzz).hash_into(values);
+    }
 }
 
 pub struct Dielectric {
@@ -99,7 +195,12 @@ impl Dielectric {
 }
 
 impl Scatter for Dielectric {
-    fn scatter(&self, ray_in: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sample: &ScatterSample,
+    ) -> Option<(Color, Ray)> {
         let refraction_ratio = if hit_record.front_face {
             1.0 / self.refraction_index
         } else {
@@ -110,17 +211,228 @@ impl Scatter for Dielectric {
         let cos_theta = ((-1.0) * unit_direction).dot(hit_record.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
 
-        let mut rng = rand::thread_rng();
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let will_reflect = rng.gen::<f64>() < Self::reflectance(cos_theta, refraction_ratio);
+        let will_reflect = sample.choice < Self::reflectance(cos_theta, refraction_ratio);
 
         let direction = if cannot_refract || will_reflect {
             unit_direction.reflect(hit_record.normal)
         } else {
             unit_direction.refract(hit_record.normal, refraction_ratio)
         };
-        let scattered_ray = Ray::new(hit_record.hit_point, direction);
+        let scattered_ray = Ray::new(hit_record.hit_point, direction, ray_in.time);
 
         Some((Color::new(1.0, 1.0, 1.0), scattered_ray))
     }
+
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        ("dielectric", self.refraction_index).hash_into(values);
+    }
+}
+
+/// Emitter that absorbs everything it is hit with.
+pub struct DiffuseLight {
+    emit: Color,
+    two_sided: bool,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        Self {
+            emit,
+            two_sided: false,
+        }
+    }
+
+    /// Emits from the back face as well as the front.
+    pub fn with_two_sided(mut self) -> Self {
+        self.two_sided = true;
+        self
+    }
+}
+
+impl Scatter for DiffuseLight {
+    fn scatter(
+        &self,
+        _ray_in: &Ray,
+        _hit_record: &HitRecord,
+        _sample: &ScatterSample,
+    ) -> Option<(Color, Ray)> {
+        None
+    }
+
+    fn emitted(&self, hit_record: &HitRecord) -> Color {
+        if hit_record.front_face || self.two_sided {
+            self.emit
+        } else {
+            Color::Black
+        }
+    }
+
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        ("diffuse light", self.emit, self.two_sided).hash_into(values);
+    }
+}
+
+/// Metallic-roughness material as used by glTF.
+///
+/// Each bounce either reflects specularly, with a chance that grows with
+/// metalness and Fresnel reflectance at grazing angles, or scatters
+/// diffusely. Rougher surfaces blur the specular lobe.
+pub struct PbrMaterial {
+    base_color: Color,
+    base_color_texture: Option<Arc<dyn Texture>>,
+    metallic: f64,
+    roughness: f64,
+    // Metalness in the blue channel, roughness in green
+    metallic_roughness_texture: Option<Arc<dyn Texture>>,
+    normal_texture: Option<(Arc<dyn Texture>, f64)>,
+    emissive: Color,
+    emissive_texture: Option<Arc<dyn Texture>>,
+}
+
+impl PbrMaterial {
+    pub fn new(base_color: Color, metallic: f64, roughness: f64) -> Self {
+        Self {
+            base_color,
+            base_color_texture: None,
+            metallic,
+            roughness,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            emissive: Color::Black,
+            emissive_texture: None,
+        }
+    }
+
+    pub fn with_base_color_texture(mut self, texture: Arc<dyn Texture>) -> Self {
+        self.base_color_texture = Some(texture);
+        self
+    }
+
+    pub fn with_metallic_roughness_texture(mut self, texture: Arc<dyn Texture>) -> Self {
+        self.metallic_roughness_texture = Some(texture);
+        self
+    }
+
+    /// Tangent-space normal map; `scale` strengthens or flattens the bumps.
+    pub fn with_normal_texture(mut self, texture: Arc<dyn Texture>, scale: f64) -> Self {
+        self.normal_texture = Some((texture, scale));
+        self
+    }
+
+    pub fn with_emissive(mut self, emissive: Color) -> Self {
+        self.emissive = emissive;
+        self
+    }
+
+    pub fn with_emissive_texture(mut self, texture: Arc<dyn Texture>) -> Self {
+        self.emissive_texture = Some(texture);
+        self
+    }
+
+    fn shading_normal(&self, hit_record: &HitRecord) -> Vec3D {
+        let (texture, scale, tangent) = match (&self.normal_texture, hit_record.tangent) {
+            (Some((texture, scale)), Some(tangent)) => (texture, *scale, tangent),
+            _ => return hit_record.normal,
+        };
+
+        let n = hit_record.normal;
+        // Gram-Schmidt the tangent against the normal, which may have flipped
+        let t = (tangent - tangent.dot(n) * n).normalize();
+        let b = n.cross(t);
+
+        let sample = texture.value(hit_record).to_vec3d();
+        let x = (2.0 * sample.x - 1.0) * scale;
+        let y = (2.0 * sample.y - 1.0) * scale;
+        let z = 2.0 * sample.z - 1.0;
+        let perturbed = (x * t + y * b + z * n).normalize();
+
+        if perturbed.dot(n) > 0.0 {
+            perturbed
+        } else {
+            n
+        }
+    }
+}
+
+impl Scatter for PbrMaterial {
+    fn scatter(
+        &self,
+        ray_in: &Ray,
+        hit_record: &HitRecord,
+        sample: &ScatterSample,
+    ) -> Option<(Color, Ray)> {
+        let mut base_color = self.base_color.to_vec3d();
+        if let Some(texture) = &self.base_color_texture {
+            base_color = base_color * texture.value(hit_record).to_vec3d();
+        }
+        let (mut metallic, mut roughness) = (self.metallic, self.roughness);
+        if let Some(texture) = &self.metallic_roughness_texture {
+            let sample = texture.value(hit_record).to_vec3d();
+            metallic *= sample.z;
+            roughness *= sample.y;
+        }
+
+        let normal = self.shading_normal(hit_record);
+        let unit_direction = ray_in.direction.normalize();
+        let cos_theta = ((-1.0) * unit_direction).dot(normal).clamp(0.0, 1.0);
+
+        // Dielectrics reflect about 4% head-on, metals reflect everything
+        let specular_chance = metallic + (1.0 - metallic) * Dielectric::reflectance(cos_theta, 1.5);
+
+        if sample.choice < specular_chance {
+            let reflected = unit_direction.reflect(normal);
+            let direction = reflected + roughness.powi(2) * sample.in_unit_sphere();
+            if direction.dot(hit_record.normal) <= 0.0 {
+                return None;
+            }
+            let white = Vec3D::new(1.0, 1.0, 1.0);
+            let tint = (1.0 - metallic) * white + metallic * base_color;
+            Some((
+                Color::RGB(tint),
+                Ray::new(hit_record.hit_point, direction, ray_in.time),
+            ))
+        } else {
+            let mut direction = normal + Vec3D::on_unit_sphere(sample.direction);
+            if direction.is_near_zero() {
+                direction = normal;
+            }
+            Some((
+                Color::RGB(base_color),
+                Ray::new(hit_record.hit_point, direction, ray_in.time),
+            ))
+        }
+    }
+
+    fn emitted(&self, hit_record: &HitRecord) -> Color {
+        let mut emissive = self.emissive.to_vec3d();
+        if let Some(texture) = &self.emissive_texture {
+            emissive = emissive * texture.value(hit_record).to_vec3d();
+        }
+        Color::RGB(emissive)
+    }
+
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        let (normal_texture, normal_scale) = match &self.normal_texture {
+            Some((texture, scale)) => (Some(texture), *scale),
+            None => (None, 0.0),
+        };
+        (
+            "pbr",
+            self.base_color,
+            self.metallic,
+            self.roughness,
+            normal_scale,
+            self.emissive,
+        )
+            .hash_into(values);
+        for texture in [
+            self.base_color_texture.as_ref(),
+            self.metallic_roughness_texture.as_ref(),
+            normal_texture,
+            self.emissive_texture.as_ref(),
+        ] {
+            hash_texture(texture, values);
+        }
+    }
 }
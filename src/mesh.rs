@@ -0,0 +1,267 @@
+pub mod ply;
+pub mod stl;
+
+use std::sync::Arc;
+
+use crate::{
+    bvh::{Aabb, Bvh},
+    color::Color,
+    content::HashInto,
+    hit::{Hit, HitRecord, TriangleHit},
+    material::MaterialId,
+    ray::Ray,
+    vec::{Point3D, Vec3D},
+};
+
+/// Vertex and index buffers of a triangle mesh.
+///
+/// Optional attributes are either empty or hold one entry per position.
+#[derive(Clone, Default)]
+pub struct MeshData {
+    pub positions: Vec<Point3D>,
+    pub normals: Vec<Vec3D>,
+    pub uvs: Vec<(f64, f64)>,
+    pub colors: Vec<Color>,
+    pub indices: Vec<[usize; 3]>,
+}
+
+impl HashInto for MeshData {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        self.positions.hash_into(values);
+        self.normals.hash_into(values);
+        self.uvs.hash_into(values);
+        self.colors.hash_into(values);
+        self.indices.hash_into(values);
+    }
+}
+
+impl MeshData {
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    // Moller-Trumbore intersection, returning `t` and the barycentric
+    // coordinates of the second and third vertex
+    fn intersect(
+        &self,
+        index: usize,
+        ray: &Ray,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<(f64, f64, f64)> {
+        let [i0, i1, i2] = self.indices[index];
+        let p0 = self.positions[i0];
+        let edge1 = self.positions[i1] - p0;
+        let edge2 = self.positions[i2] - p0;
+
+        let pvec = ray.direction.cross(edge2);
+        let det = edge1.dot(pvec);
+        if det.abs() < 1.0e-12 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let tvec = ray.origin - p0;
+        let b1 = tvec.dot(pvec) * inv_det;
+        if !(0.0..=1.0).contains(&b1) {
+            return None;
+        }
+
+        let qvec = tvec.cross(edge1);
+        let b2 = ray.direction.dot(qvec) * inv_det;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return None;
+        }
+
+        let t = edge2.dot(qvec) * inv_det;
+        if t < t_min || t_max < t {
+            return None;
+        }
+
+        Some((t, b1, b2))
+    }
+}
+
+/// Triangle mesh sharing its buffers with every other mesh built from the
+/// same `MeshData`, so only the material differs between copies.
+#[derive(Clone)]
+pub struct TriangleMesh {
+    pub data: Arc<MeshData>,
+    pub material: MaterialId,
+    bvh: Arc<Bvh>,
+}
+
+impl TriangleMesh {
+    pub fn new(data: Arc<MeshData>, material: MaterialId) -> Self {
+        let bounds: Vec<Aabb> = data
+            .indices
+            .iter()
+            .map(|triangle| Aabb::from_points(triangle.iter().map(|&i| data.positions[i])).unwrap())
+            .collect();
+        Self {
+            bvh: Arc::new(Bvh::new(&bounds)),
+            data,
+            material,
+        }
+    }
+
+    fn record(&self, ray: &Ray, index: usize, t: f64, b1: f64, b2: f64) -> HitRecord {
+        let data = &self.data;
+        let [i0, i1, i2] = data.indices[index];
+        let b0 = 1.0 - b1 - b2;
+
+        let geometric_normal = (data.positions[i1] - data.positions[i0])
+            .cross(data.positions[i2] - data.positions[i0])
+            .normalize();
+        let mut hit_record = HitRecord::new(ray.at(t), geometric_normal, self.material, t, ray)
+            .with_triangle(TriangleHit {
+                mesh: data.clone(),
+                index,
+                b1,
+                b2,
+            });
+
+        if !data.normals.is_empty() {
+            // Shade with the interpolated normal, kept on the side the ray came from
+            let shading_normal =
+                (b0 * data.normals[i0] + b1 * data.normals[i1] + b2 * data.normals[i2]).normalize();
+            hit_record.normal = if shading_normal.dot(hit_record.normal) < 0.0 {
+                -1.0 * shading_normal
+            } else {
+                shading_normal
+            };
+        }
+
+        if data.uvs.is_empty() {
+            return hit_record.with_uv(b1, b2);
+        }
+
+        let (u0, v0) = data.uvs[i0];
+        let (u1, v1) = data.uvs[i1];
+        let (u2, v2) = data.uvs[i2];
+        hit_record = hit_record.with_uv(b0 * u0 + b1 * u1 + b2 * u2, b0 * v0 + b1 * v1 + b2 * v2);
+
+        // Solve for dp/du from the two edges and their texture-space deltas
+        let (du02, dv02) = (u0 - u2, v0 - v2);
+        let (du12, dv12) = (u1 - u2, v1 - v2);
+        let determinant = du02 * dv12 - dv02 * du12;
+        if determinant.abs() < 1.0e-12 {
+            return hit_record;
+        }
+        let dp02 = data.positions[i0] - data.positions[i2];
+        let dp12 = data.positions[i1] - data.positions[i2];
+        let dpdu = (dv12 * dp02 - dv02 * dp12) / determinant;
+        if dpdu.is_near_zero() {
+            hit_record
+        } else {
+            hit_record.with_tangent(dpdu.normalize())
+        }
+    }
+}
+
+impl Hit for TriangleMesh {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let closest = self.bvh.hit(ray, t_min, t_max, |index, closest_so_far| {
+            let (t, b1, b2) = self.data.intersect(index, ray, t_min, closest_so_far)?;
+            Some((t, (index, t, b1, b2)))
+        });
+
+        closest.map(|(index, t, b1, b2)| self.record(ray, index, t, b1, b2))
+    }
+
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<f64> {
+        self.bvh.hit(ray, t_min, t_max, |index, closest_so_far| {
+            let (t, _, _) = self.data.intersect(index, ray, t_min, closest_so_far)?;
+            Some((t, t))
+        })
+    }
+
+    fn bounding_box(&self) -> Option<Aabb> {
+        Aabb::from_points(self.data.positions.iter().copied())
+    }
+
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        ("triangle mesh", &self.data, self.material).hash_into(values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{texture::Texture, texture::VertexColor};
+
+    #[test]
+    fn test_triangle_mesh_hit_and_vertex_colors() {
+        let data = Arc::new(MeshData {
+            positions: vec![
+                Point3D::new(0.0, 0.0, 0.0),
+                Point3D::new(1.0, 0.0, 0.0),
+                Point3D::new(0.0, 1.0, 0.0),
+            ],
+            colors: vec![Color::Red, Color::Green, Color::Blue],
+            indices: vec![[0, 1, 2]],
+            ..MeshData::default()
+        });
+        let mesh = TriangleMesh::new(data.clone(), MaterialId(0));
+
+        let ray = Ray::new(
+            Point3D::new(0.25, 0.25, 1.0),
+            Vec3D::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+        let hit = mesh.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 1.0).abs() < 1.0e-12);
+        assert!(hit.front_face);
+        assert_eq!(hit.normal, Vec3D::new(0.0, 0.0, 1.0));
+
+        let color = VertexColor::new(data).value(&hit).to_vec3d();
+        assert!((color - Vec3D::new(0.5, 0.25, 0.25)).length() < 1.0e-12);
+
+        let miss = Ray::new(
+            Point3D::new(0.75, 0.75, 1.0),
+            Vec3D::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+        assert!(mesh.hit(&miss, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_vertex_color_ignores_other_meshes() {
+        let triangle = |z: f64, colors: Vec<Color>| {
+            Arc::new(MeshData {
+                positions: vec![
+                    Point3D::new(0.0, 0.0, z),
+                    Point3D::new(1.0, 0.0, z),
+                    Point3D::new(0.0, 1.0, z),
+                    Point3D::new(1.0, 1.0, z),
+                ],
+                colors,
+                indices: vec![[0, 1, 2], [1, 3, 2]],
+                ..MeshData::default()
+            })
+        };
+        let colored = triangle(0.0, vec![Color::Red; 4]);
+        // Same layout with its own colors, and one without any
+        let recolored = triangle(-1.0, vec![Color::Blue; 4]);
+        let plain = triangle(-2.0, Vec::new());
+        let texture = VertexColor::new(colored.clone()).with_fallback(Color::Green);
+
+        let ray = Ray::new(
+            Point3D::new(0.75, 0.75, 1.0),
+            Vec3D::new(0.0, 0.0, -1.0),
+            0.0,
+        );
+        for (data, expected) in [
+            (colored, Color::Red),
+            (recolored, Color::Green),
+            (plain, Color::Green),
+        ] {
+            let hit = TriangleMesh::new(data, MaterialId(0))
+                .hit(&ray, 0.001, f64::INFINITY)
+                .unwrap();
+            assert_eq!(hit.triangle.as_ref().unwrap().index, 1);
+            let color = texture.value(&hit).to_vec3d();
+            assert!((color - expected.to_vec3d()).length() < 1.0e-12);
+        }
+    }
+}
@@ -0,0 +1,476 @@
+// Stanford PLY in ASCII and binary encodings. Vertex positions, normals,
+// texture coordinates and colors are read; polygon faces are fanned into
+// triangles. Any other element or property is parsed and skipped.
+
+use std::{fs, io, path::Path, str::SplitAsciiWhitespace};
+
+use super::MeshData;
+use crate::{
+    color::Color,
+    vec::{Point3D, Vec3D},
+};
+
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<MeshData> {
+    parse(&fs::read(path)?)
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> io::Result<Self> {
+        Ok(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return Err(invalid(format!("unknown PLY scalar type `{}`", name))),
+        })
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    // Scale that maps integer color channels onto [0, 1]
+    fn color_scale(&self) -> f64 {
+        match self {
+            Scalar::U8 => 1.0 / 255.0,
+            Scalar::U16 => 1.0 / 65535.0,
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PropertyKind {
+    Scalar(Scalar),
+    List { count: Scalar, item: Scalar },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+    body_offset: usize,
+}
+
+fn parse_header(bytes: &[u8]) -> io::Result<Header> {
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut offset = 0;
+
+    for (number, line) in bytes.split(|&b| b == b'\n').enumerate() {
+        offset += line.len() + 1;
+        let line = std::str::from_utf8(line)
+            .map_err(|_| invalid("PLY header is not valid text"))?
+            .trim();
+        let mut words = line.split_ascii_whitespace();
+
+        if number == 0 {
+            if line != "ply" {
+                return Err(invalid("missing `ply` magic number"));
+            }
+            continue;
+        }
+
+        match words.next() {
+            Some("format") => {
+                format = Some(match words.next() {
+                    Some("ascii") => Format::Ascii,
+                    Some("binary_little_endian") => Format::BinaryLittleEndian,
+                    Some("binary_big_endian") => Format::BinaryBigEndian,
+                    other => return Err(invalid(format!("unknown PLY format {:?}", other))),
+                });
+            }
+            Some("element") => {
+                let name = words
+                    .next()
+                    .ok_or_else(|| invalid("element without a name"))?;
+                let count = words
+                    .next()
+                    .and_then(|count| count.parse().ok())
+                    .ok_or_else(|| invalid(format!("element `{}` has no count", name)))?;
+                elements.push(Element {
+                    name: name.to_string(),
+                    count,
+                    properties: Vec::new(),
+                });
+            }
+            Some("property") => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| invalid("property declared before any element"))?;
+                let kind = match words.next() {
+                    Some("list") => {
+                        let count = Scalar::parse(words.next().unwrap_or_default())?;
+                        let item = Scalar::parse(words.next().unwrap_or_default())?;
+                        PropertyKind::List { count, item }
+                    }
+                    Some(scalar) => PropertyKind::Scalar(Scalar::parse(scalar)?),
+                    None => return Err(invalid("property without a type")),
+                };
+                let name = words
+                    .next()
+                    .ok_or_else(|| invalid("property without a name"))?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind,
+                });
+            }
+            Some("end_header") => {
+                return Ok(Header {
+                    format: format.ok_or_else(|| invalid("PLY header has no format line"))?,
+                    elements,
+                    body_offset: offset.min(bytes.len()),
+                });
+            }
+            // comment, obj_info and blank lines
+            _ => {}
+        }
+    }
+
+    Err(invalid("PLY header is missing `end_header`"))
+}
+
+trait ValueReader {
+    fn read(&mut self, scalar: Scalar) -> io::Result<f64>;
+}
+
+struct AsciiReader<'a> {
+    tokens: SplitAsciiWhitespace<'a>,
+}
+
+impl ValueReader for AsciiReader<'_> {
+    fn read(&mut self, _scalar: Scalar) -> io::Result<f64> {
+        let token = self
+            .tokens
+            .next()
+            .ok_or_else(|| invalid("unexpected end of PLY data"))?;
+        token
+            .parse()
+            .map_err(|_| invalid(format!("`{}` is not a number", token)))
+    }
+}
+
+struct BinaryReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    big_endian: bool,
+}
+
+impl ValueReader for BinaryReader<'_> {
+    fn read(&mut self, scalar: Scalar) -> io::Result<f64> {
+        let size = scalar.size();
+        let end = self.position + size;
+        if end > self.bytes.len() {
+            return Err(invalid("unexpected end of PLY data"));
+        }
+        let mut raw = [0u8; 8];
+        raw[..size].copy_from_slice(&self.bytes[self.position..end]);
+        if self.big_endian {
+            raw[..size].reverse();
+        }
+        self.position = end;
+
+        Ok(match scalar {
+            Scalar::I8 => raw[0] as i8 as f64,
+            Scalar::U8 => raw[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([raw[0], raw[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([raw[0], raw[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+            Scalar::U32 => u32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+            Scalar::F32 => f32::from_le_bytes([raw[0], raw[1], raw[2], raw[3]]) as f64,
+            Scalar::F64 => f64::from_le_bytes(raw),
+        })
+    }
+}
+
+pub fn parse(bytes: &[u8]) -> io::Result<MeshData> {
+    let header = parse_header(bytes)?;
+    let body = &bytes[header.body_offset..];
+
+    match header.format {
+        Format::Ascii => {
+            let text =
+                std::str::from_utf8(body).map_err(|_| invalid("PLY body is not valid text"))?;
+            let mut reader = AsciiReader {
+                tokens: text.split_ascii_whitespace(),
+            };
+            read_elements(&header.elements, &mut reader)
+        }
+        Format::BinaryLittleEndian | Format::BinaryBigEndian => {
+            let mut reader = BinaryReader {
+                bytes: body,
+                position: 0,
+                big_endian: header.format == Format::BinaryBigEndian,
+            };
+            read_elements(&header.elements, &mut reader)
+        }
+    }
+}
+
+fn read_elements(elements: &[Element], reader: &mut dyn ValueReader) -> io::Result<MeshData> {
+    let mut mesh = MeshData::default();
+
+    for element in elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(element, reader, &mut mesh)?,
+            "face" => read_faces(element, reader, &mut mesh)?,
+            _ => {
+                for _ in 0..element.count {
+                    for property in &element.properties {
+                        read_property(property, reader)?;
+                    }
+                }
+            }
+        }
+    }
+
+    if let Some(&index) = mesh
+        .indices
+        .iter()
+        .flatten()
+        .find(|&&i| i >= mesh.positions.len())
+    {
+        return Err(invalid(format!("face refers to missing vertex {}", index)));
+    }
+
+    Ok(mesh)
+}
+
+// Whole, non-negative numbers, which may come from float properties
+fn to_index(value: f64) -> Option<usize> {
+    (value >= 0.0 && value.fract() == 0.0).then_some(value as usize)
+}
+
+fn read_property(property: &Property, reader: &mut dyn ValueReader) -> io::Result<Vec<f64>> {
+    match property.kind {
+        PropertyKind::Scalar(scalar) => Ok(vec![reader.read(scalar)?]),
+        PropertyKind::List { count, item } => {
+            let count = reader.read(count)?;
+            let count = to_index(count)
+                .ok_or_else(|| invalid(format!("`{}` is not a list length", count)))?;
+            (0..count).map(|_| reader.read(item)).collect()
+        }
+    }
+}
+
+fn read_vertices(
+    element: &Element,
+    reader: &mut dyn ValueReader,
+    mesh: &mut MeshData,
+) -> io::Result<()> {
+    let slot = |names: &[&str]| {
+        element
+            .properties
+            .iter()
+            .position(|property| names.contains(&property.name.as_str()))
+    };
+    let position = [slot(&["x"]), slot(&["y"]), slot(&["z"])];
+    let normal = [slot(&["nx"]), slot(&["ny"]), slot(&["nz"])];
+    let uv = [
+        slot(&["u", "s", "texture_u", "texture_s"]),
+        slot(&["v", "t", "texture_v", "texture_t"]),
+    ];
+    let color = [
+        slot(&["red", "r", "diffuse_red"]),
+        slot(&["green", "g", "diffuse_green"]),
+        slot(&["blue", "b", "diffuse_blue"]),
+    ];
+
+    let [Some(x), Some(y), Some(z)] = position else {
+        return Err(invalid("PLY vertices need x, y and z properties"));
+    };
+    let normal = match normal {
+        [Some(x), Some(y), Some(z)] => Some([x, y, z]),
+        _ => None,
+    };
+    let uv = match uv {
+        [Some(u), Some(v)] => Some([u, v]),
+        _ => None,
+    };
+    let color = match color {
+        [Some(r), Some(g), Some(b)] => Some([r, g, b]),
+        _ => None,
+    };
+
+    let mut values = vec![0.0; element.properties.len()];
+    for _ in 0..element.count {
+        for (value, property) in values.iter_mut().zip(&element.properties) {
+            *value = read_property(property, reader)?
+                .first()
+                .copied()
+                .unwrap_or(0.0);
+        }
+
+        mesh.positions
+            .push(Point3D::new(values[x], values[y], values[z]));
+        if let Some([x, y, z]) = normal {
+            mesh.normals
+                .push(Vec3D::new(values[x], values[y], values[z]));
+        }
+        if let Some([u, v]) = uv {
+            mesh.uvs.push((values[u], values[v]));
+        }
+        if let Some(channels) = color {
+            let [r, g, b] = channels.map(|i| match element.properties[i].kind {
+                PropertyKind::Scalar(scalar) => values[i] * scalar.color_scale(),
+                PropertyKind::List { .. } => values[i],
+            });
+            mesh.colors.push(Color::new(r, g, b));
+        }
+    }
+
+    Ok(())
+}
+
+fn read_faces(
+    element: &Element,
+    reader: &mut dyn ValueReader,
+    mesh: &mut MeshData,
+) -> io::Result<()> {
+    let indices = element
+        .properties
+        .iter()
+        .position(|property| property.name == "vertex_indices" || property.name == "vertex_index")
+        .ok_or_else(|| invalid("PLY faces need a vertex_indices list"))?;
+
+    for face in 0..element.count {
+        for (i, property) in element.properties.iter().enumerate() {
+            let values = read_property(property, reader)?;
+            if i != indices {
+                continue;
+            }
+            let polygon = values
+                .iter()
+                .map(|&index| {
+                    to_index(index).ok_or_else(|| {
+                        invalid(format!("face {}: `{}` is not a vertex index", face, index))
+                    })
+                })
+                .collect::<io::Result<Vec<usize>>>()?;
+            // Fan-triangulate polygons
+            for k in 1..polygon.len().saturating_sub(1) {
+                mesh.indices.push([polygon[0], polygon[k], polygon[k + 1]]);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ascii_ply_with_colors() {
+        let text = "ply
+format ascii 1.0
+comment a colored quad
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0
+1 0 0 0 255 0
+1 1 0 0 0 255
+0 1 0 255 255 255
+4 0 1 2 3
+";
+        let mesh = parse(text.as_bytes()).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.colors.len(), 4);
+        assert_eq!(mesh.colors[1].to_vec3d(), Vec3D::new(0.0, 1.0, 0.0));
+        assert!(mesh.normals.is_empty());
+
+        for face in ["4 0 1 -2 3", "4 0 1 2.5 3", "-1 0 1 2"] {
+            let text = text.replace("4 0 1 2 3", face);
+            assert!(parse(text.as_bytes()).is_err(), "{}", face);
+        }
+    }
+
+    #[test]
+    fn test_parse_binary_ply() {
+        let mut bytes = b"ply
+format binary_big_endian 1.0
+element vertex 3
+property double x
+property double y
+property double z
+property float confidence
+element face 1
+property uchar flags
+property list uchar uint vertex_indices
+end_header
+"
+        .to_vec();
+        for (x, y, z) in [(0.0f64, 0.0f64, 0.0f64), (1.0, 0.0, 0.0), (0.0, 1.0, 0.0)] {
+            bytes.extend(x.to_be_bytes());
+            bytes.extend(y.to_be_bytes());
+            bytes.extend(z.to_be_bytes());
+            bytes.extend(0.5f32.to_be_bytes());
+        }
+        bytes.push(7);
+        bytes.push(3);
+        for index in [0u32, 1, 2] {
+            bytes.extend(index.to_be_bytes());
+        }
+
+        let mesh = parse(&bytes).unwrap();
+        assert_eq!(mesh.positions[1], Point3D::new(1.0, 0.0, 0.0));
+        assert_eq!(mesh.indices, vec![[0, 1, 2]]);
+
+        bytes.truncate(bytes.len() - 2);
+        assert!(parse(&bytes).is_err());
+    }
+}
@@ -0,0 +1,157 @@
+// STL in ASCII and binary encodings. STL stores every triangle with its own
+// three corners, so identical corners are merged to share vertices.
+
+use std::{collections::HashMap, fs, io, path::Path};
+
+use super::MeshData;
+use crate::vec::Point3D;
+
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<MeshData> {
+    parse(&fs::read(path)?)
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+pub fn parse(bytes: &[u8]) -> io::Result<MeshData> {
+    // Binary files are allowed to start with "solid" too, so trust the size
+    // implied by the triangle count before looking at the first word
+    if bytes.len() >= 84 {
+        let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+        if bytes.len() == 84 + 50 * count {
+            return parse_binary(bytes, count);
+        }
+    }
+    if bytes.trim_ascii_start().starts_with(b"solid") {
+        parse_ascii(bytes)
+    } else {
+        Err(invalid("not an STL file"))
+    }
+}
+
+#[derive(Default)]
+struct Builder {
+    mesh: MeshData,
+    vertices: HashMap<[u64; 3], usize>,
+}
+
+impl Builder {
+    fn vertex(&mut self, p: Point3D) -> usize {
+        let key = [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()];
+        let positions = &mut self.mesh.positions;
+        *self.vertices.entry(key).or_insert_with(|| {
+            positions.push(p);
+            positions.len() - 1
+        })
+    }
+
+    fn triangle(&mut self, corners: [Point3D; 3]) {
+        let indices = corners.map(|p| self.vertex(p));
+        self.mesh.indices.push(indices);
+    }
+}
+
+fn parse_binary(bytes: &[u8], count: usize) -> io::Result<MeshData> {
+    let mut builder = Builder::default();
+    let read_f32 = |offset: usize| {
+        f32::from_le_bytes([
+            bytes[offset],
+            bytes[offset + 1],
+            bytes[offset + 2],
+            bytes[offset + 3],
+        ]) as f64
+    };
+
+    for i in 0..count {
+        // Facet normal (12 bytes), three corners (36 bytes), attributes (2 bytes)
+        let facet = 84 + 50 * i;
+        let corner = |k: usize| {
+            let offset = facet + 12 + 12 * k;
+            Point3D::new(read_f32(offset), read_f32(offset + 4), read_f32(offset + 8))
+        };
+        builder.triangle([corner(0), corner(1), corner(2)]);
+    }
+
+    Ok(builder.mesh)
+}
+
+fn parse_ascii(bytes: &[u8]) -> io::Result<MeshData> {
+    let text = std::str::from_utf8(bytes).map_err(|_| invalid("ASCII STL is not valid text"))?;
+    let mut builder = Builder::default();
+    let mut corners = Vec::with_capacity(3);
+    let mut words = text.split_ascii_whitespace();
+
+    while let Some(word) = words.next() {
+        match word {
+            "vertex" => {
+                let mut coordinate = || -> io::Result<f64> {
+                    let token = words
+                        .next()
+                        .ok_or_else(|| invalid("unexpected end of STL data"))?;
+                    token
+                        .parse()
+                        .map_err(|_| invalid(format!("`{}` is not a number", token)))
+                };
+                corners.push(Point3D::new(coordinate()?, coordinate()?, coordinate()?));
+            }
+            "endloop" => {
+                if corners.len() != 3 {
+                    return Err(invalid(format!("facet with {} vertices", corners.len())));
+                }
+                builder.triangle([corners[0], corners[1], corners[2]]);
+                corners.clear();
+            }
+            _ => {}
+        }
+    }
+
+    Ok(builder.mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ascii_stl() {
+        let text = "solid square
+facet normal 0 0 1
+  outer loop
+    vertex 0 0 0
+    vertex 1 0 0
+    vertex 1 1 0
+  endloop
+endfacet
+facet normal 0 0 1
+  outer loop
+    vertex 0 0 0
+    vertex 1 1 0
+    vertex 0 1 0
+  endloop
+endfacet
+endsolid square
+";
+        let mesh = parse(text.as_bytes()).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn test_parse_binary_stl() {
+        // Header deliberately starts with "solid" like many exporters write
+        let mut bytes = b"solid exported".to_vec();
+        bytes.resize(80, 0);
+        bytes.extend(1u32.to_le_bytes());
+        for value in [
+            0.0f32, 0.0, 1.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 2.0, 0.0,
+        ] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend([0, 0]);
+
+        let mesh = parse(&bytes).unwrap();
+        assert_eq!(mesh.indices, vec![[0, 1, 2]]);
+        assert_eq!(mesh.positions[2], Point3D::new(0.0, 2.0, 0.0));
+    }
+}
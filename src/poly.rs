@@ -0,0 +1,237 @@
+// Polynomial root finding used by the analytic primitives.
+
+use std::f64::consts::PI;
+
+/// Real roots of `a*t^2 + b*t + c = 0` in ascending order.
+///
+/// Uses the numerically stable form that avoids cancellation between `-b`
+/// and the square root of the discriminant. A vanishing `a` degenerates to
+/// the linear case, in which both returned roots are equal.
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Option<(f64, f64)> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        let t = -c / b;
+        return Some((t, t));
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let sqrt_discriminant = discriminant.sqrt();
+    let q = if b < 0.0 {
+        -0.5 * (b - sqrt_discriminant)
+    } else {
+        -0.5 * (b + sqrt_discriminant)
+    };
+
+    let (t0, t1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+
+    if t0 <= t1 {
+        Some((t0, t1))
+    } else {
+        Some((t1, t0))
+    }
+}
+
+const EPS: f64 = 1.0e-12;
+
+fn is_zero(x: f64) -> bool {
+    x.abs() < EPS
+}
+
+/// Real roots of `a*t^3 + b*t^2 + c*t + d = 0` in ascending order.
+pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> Vec<f64> {
+    if a == 0.0 {
+        return solve_quadratic(b, c, d).map_or(Vec::new(), |(t0, t1)| vec![t0, t1]);
+    }
+
+    // Normal form t^3 + A*t^2 + B*t + C, substituted t = s - A/3 to remove
+    // the quadratic term: s^3 + 3*p*s + 2*q = 0
+    let (a, b, c) = (b / a, c / a, d / a);
+    let sq_a = a * a;
+    let p = (-sq_a / 3.0 + b) / 3.0;
+    let q = (2.0 / 27.0 * a * sq_a - a * b / 3.0 + c) / 2.0;
+
+    let cb_p = p * p * p;
+    let discriminant = q * q + cb_p;
+
+    let mut roots = if is_zero(discriminant) {
+        if is_zero(q) {
+            vec![0.0]
+        } else {
+            let u = (-q).cbrt();
+            vec![2.0 * u, -u]
+        }
+    } else if discriminant < 0.0 {
+        // Casus irreducibilis: three real roots
+        let phi = (-q / (-cb_p).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        vec![
+            t * phi.cos(),
+            -t * (phi + PI / 3.0).cos(),
+            -t * (phi - PI / 3.0).cos(),
+        ]
+    } else {
+        let sqrt_discriminant = discriminant.sqrt();
+        let u = (sqrt_discriminant - q).cbrt();
+        let v = -(sqrt_discriminant + q).cbrt();
+        vec![u + v]
+    };
+
+    for root in roots.iter_mut() {
+        *root -= a / 3.0;
+    }
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+/// Real roots of `a*t^4 + b*t^3 + c*t^2 + d*t + e = 0` in ascending order.
+///
+/// Ferrari's method reduces the quartic to a resolvent cubic and two
+/// quadratics. The closed form loses precision when the roots are far apart,
+/// so each root is refined with a few Newton steps on the original
+/// polynomial before being returned.
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Vec<f64> {
+    if a == 0.0 {
+        return solve_cubic(b, c, d, e);
+    }
+
+    // Normal form t^4 + A*t^3 + B*t^2 + C*t + D, substituted t = s - A/4 to
+    // remove the cubic term: s^4 + p*s^2 + q*s + r = 0
+    let (na, nb, nc, nd) = (b / a, c / a, d / a, e / a);
+    let sq_a = na * na;
+    let p = -3.0 / 8.0 * sq_a + nb;
+    let q = sq_a * na / 8.0 - na * nb / 2.0 + nc;
+    let r = -3.0 / 256.0 * sq_a * sq_a + sq_a * nb / 16.0 - na * nc / 4.0 + nd;
+
+    let mut roots = if is_zero(r) {
+        // No absolute term: s * (s^3 + p*s + q) = 0
+        let mut roots = solve_cubic(1.0, 0.0, p, q);
+        roots.push(0.0);
+        roots
+    } else {
+        let z = solve_cubic(1.0, -p / 2.0, -r, r * p / 2.0 - q * q / 8.0)
+            .into_iter()
+            .last();
+        let z = match z {
+            Some(z) => z,
+            None => return Vec::new(),
+        };
+
+        let u = z * z - r;
+        let v = 2.0 * z - p;
+        let u = if is_zero(u) {
+            0.0
+        } else if u > 0.0 {
+            u.sqrt()
+        } else {
+            return Vec::new();
+        };
+        let v = if is_zero(v) {
+            0.0
+        } else if v > 0.0 {
+            v.sqrt()
+        } else {
+            return Vec::new();
+        };
+        let v = if q < 0.0 { -v } else { v };
+
+        let mut roots = Vec::with_capacity(4);
+        if let Some((s0, s1)) = solve_quadratic(1.0, v, z - u) {
+            roots.extend([s0, s1]);
+        }
+        if let Some((s0, s1)) = solve_quadratic(1.0, -v, z + u) {
+            roots.extend([s0, s1]);
+        }
+        roots
+    };
+
+    for root in roots.iter_mut() {
+        *root = polish_root(*root - na / 4.0, [a, b, c, d, e]);
+    }
+    roots.sort_by(f64::total_cmp);
+    roots.dedup();
+    roots
+}
+
+fn polish_root(mut t: f64, coefficients: [f64; 5]) -> f64 {
+    let [a, b, c, d, e] = coefficients;
+    for _ in 0..3 {
+        let f = (((a * t + b) * t + c) * t + d) * t + e;
+        let df = ((4.0 * a * t + 3.0 * b) * t + 2.0 * c) * t + d;
+        if df == 0.0 {
+            break;
+        }
+        let step = f / df;
+        t -= step;
+        if step.abs() <= EPS * t.abs() {
+            break;
+        }
+    }
+    t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solve_quadratic() {
+        assert_eq!(solve_quadratic(1.0, -3.0, 2.0), Some((1.0, 2.0)));
+        assert_eq!(solve_quadratic(1.0, 0.0, 1.0), None);
+        assert_eq!(solve_quadratic(0.0, 2.0, -4.0), Some((2.0, 2.0)));
+    }
+
+    #[test]
+    fn test_solve_quadratic_cancellation() {
+        // Roots 1e-9 and 1e9: the naive formula loses the small root entirely
+        let (t0, t1) = solve_quadratic(1.0, -(1.0e9 + 1.0e-9), 1.0).unwrap();
+        assert!((t0 - 1.0e-9).abs() < 1.0e-18);
+        assert!((t1 - 1.0e9).abs() < 1.0e-3);
+    }
+
+    fn assert_roots(roots: Vec<f64>, expected: &[f64]) {
+        assert_eq!(roots.len(), expected.len(), "{:?} != {:?}", roots, expected);
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!(
+                (root - expected).abs() < 1.0e-9,
+                "{:?} != {:?}",
+                roots,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_solve_cubic() {
+        // (t - 1)(t - 2)(t - 3)
+        assert_roots(solve_cubic(1.0, -6.0, 11.0, -6.0), &[1.0, 2.0, 3.0]);
+        // (t + 2)(t^2 + 1)
+        assert_roots(solve_cubic(2.0, 4.0, 2.0, 4.0), &[-2.0]);
+    }
+
+    #[test]
+    fn test_solve_quartic() {
+        // (t - 1)(t - 2)(t - 3)(t - 4)
+        assert_roots(
+            solve_quartic(1.0, -10.0, 35.0, -50.0, 24.0),
+            &[1.0, 2.0, 3.0, 4.0],
+        );
+        // (t^2 - 4)(t^2 + 1)
+        assert_roots(solve_quartic(1.0, 0.0, -3.0, 0.0, -4.0), &[-2.0, 2.0]);
+        // t^4 + 1 has no real roots
+        assert_roots(solve_quartic(1.0, 0.0, 0.0, 0.0, 1.0), &[]);
+    }
+
+    #[test]
+    fn test_solve_quartic_spread_roots() {
+        // (t - 0.001)(t - 1)(t - 100)(t - 1000)
+        let coefficients = [1.0, -1101.001, 101101.101, -100101.1, 100.0];
+        let [a, b, c, d, e] = coefficients;
+        assert_roots(solve_quartic(a, b, c, d, e), &[0.001, 1.0, 100.0, 1000.0]);
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// How far a render has got, handed to the progress callback each time a
+/// tile finishes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    pub tiles_done: usize,
+    /// Tiles to render in all, or 0 if the render runs until its time
+    /// budget is spent.
+    pub tiles: usize,
+    /// Pixels rendered, counted again on every pass over the image.
+    pub pixels_done: usize,
+    pub pixels: usize,
+    /// Camera samples traced so far.
+    pub samples: u64,
+    /// Rays cast against the scene so far, camera rays and bounces alike.
+    pub rays: u64,
+    pub elapsed: Duration,
+    /// Time the render may take.
+    pub budget: Option<Duration>,
+}
+
+impl Progress {
+    /// Share of the work finished, from 0 to 1: of the pixels, or of the
+    /// time budget if that runs out first.
+    pub fn fraction(&self) -> f64 {
+        let work = if self.pixels > 0 {
+            self.pixels_done as f64 / self.pixels as f64
+        } else {
+            0.0
+        };
+        let time = self.budget.map_or(0.0, |budget| {
+            self.elapsed.as_secs_f64() / budget.as_secs_f64()
+        });
+        work.max(time).min(1.0)
+    }
+
+    pub fn rays_per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.rays as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// Time left if the rest of the image renders as fast as what is done,
+    /// or `None` before anything is.
+    pub fn eta(&self) -> Option<Duration> {
+        let fraction = self.fraction();
+        if fraction <= 0.0 {
+            return None;
+        }
+        Some(self.elapsed.mul_f64((1.0 - fraction) / fraction))
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.fraction() >= 1.0
+    }
+}
+
+/// Called with every update; shared by the threads rendering tiles.
+pub type ProgressCallback = dyn Fn(&Progress) + Send + Sync;
+
+// Running totals of one render. Updates are made and reported under the
+// lock, so the callback sees them one at a time and in order.
+pub(crate) struct Tracker<'a> {
+    start: Instant,
+    progress: Mutex<Progress>,
+    callback: Option<&'a ProgressCallback>,
+}
+
+impl<'a> Tracker<'a> {
+    pub(crate) fn new(
+        tiles: usize,
+        pixels: usize,
+        budget: Option<Duration>,
+        callback: Option<&'a ProgressCallback>,
+    ) -> Self {
+        Self {
+            start: Instant::now(),
+            progress: Mutex::new(Progress {
+                tiles_done: 0,
+                tiles,
+                pixels_done: 0,
+                pixels,
+                samples: 0,
+                rays: 0,
+                elapsed: Duration::ZERO,
+                budget,
+            }),
+            callback,
+        }
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub(crate) fn tile_done(&self, pixels: usize, samples: u64, rays: u64) {
+        let mut progress = self.progress.lock().unwrap();
+        progress.tiles_done += 1;
+        progress.pixels_done += pixels;
+        progress.samples += samples;
+        progress.rays += rays;
+        progress.elapsed = self.start.elapsed();
+        if let Some(callback) = self.callback {
+            callback(&progress);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eta_scales_elapsed_time() {
+        let mut progress = Progress {
+            tiles_done: 0,
+            tiles: 4,
+            pixels_done: 0,
+            pixels: 400,
+            samples: 0,
+            rays: 0,
+            elapsed: Duration::from_secs(1),
+            budget: None,
+        };
+        assert_eq!(progress.eta(), None);
+
+        progress.tiles_done = 1;
+        progress.pixels_done = 100;
+        progress.rays = 5000;
+        assert_eq!(progress.fraction(), 0.25);
+        assert_eq!(progress.eta(), Some(Duration::from_secs(3)));
+        assert_eq!(progress.rays_per_second(), 5000.0);
+        assert!(!progress.is_done());
+
+        // Past half the time budget, that is the better estimate
+        progress.budget = Some(Duration::from_secs(2));
+        assert_eq!(progress.fraction(), 0.5);
+        assert_eq!(progress.eta(), Some(Duration::from_secs(1)));
+        progress.elapsed = Duration::from_secs(3);
+        assert!(progress.is_done());
+    }
+}
@@ -0,0 +1,42 @@
+use rand_pcg::Pcg32;
+
+/// Generator every sampling routine draws from while rendering.
+pub type SampleRng = Pcg32;
+
+// SplitMix64 finalizer, to spread nearby pixel and sample indices over the
+// whole state space
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Combines integers into one well-mixed value, for decorrelating
+/// sequences per pixel and dimension.
+pub fn hash(values: &[u64]) -> u64 {
+    values
+        .iter()
+        .fold(0x9e37_79b9_7f4a_7c15, |h, &v| mix(h ^ mix(v)))
+}
+
+/// Generator for one sample of one pixel. It depends only on its arguments,
+/// so an image is the same however its pixels are spread over threads.
+pub fn sample_rng(seed: u64, pixel: u64, sample: u64) -> SampleRng {
+    let state = mix(mix(seed ^ mix(pixel)).wrapping_add(sample));
+    SampleRng::new(state, seed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_sample_rng_is_reproducible() {
+        let draw = |seed, pixel, sample| sample_rng(seed, pixel, sample).gen::<u64>();
+        assert_eq!(draw(1, 2, 3), draw(1, 2, 3));
+        assert_ne!(draw(1, 2, 3), draw(1, 2, 4));
+        assert_ne!(draw(1, 2, 3), draw(1, 3, 3));
+        assert_ne!(draw(1, 2, 3), draw(2, 2, 3));
+    }
+}
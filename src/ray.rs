@@ -1,48 +1,73 @@
 use crate::{
     color::Color,
     hit::Hit,
-    object::Sphere,
+    material::ScatterSample,
+    sampler::Sampler,
+    scene::Scene,
     vec::{Point3D, Vec3D},
-    World,
 };
 
 #[derive(Debug, PartialEq)]
 pub struct Ray {
     pub origin: Point3D,
     pub direction: Vec3D,
+    pub time: f64,
 }
 
 impl Ray {
-    pub fn new(origin: Point3D, direction: Vec3D) -> Self {
-        Self { origin, direction }
+    pub fn new(origin: Point3D, direction: Vec3D, time: f64) -> Self {
+        Self {
+            origin,
+            direction,
+            time,
+        }
     }
 
     pub fn at(&self, t: f64) -> Point3D {
         self.origin + t * self.direction
     }
 
-    pub fn color(&self, world: &World, ray_bounce_depth: usize) -> Color {
-        if ray_bounce_depth <= 0 {
+    pub fn color(
+        &self,
+        scene: &Scene,
+        ray_bounce_depth: usize,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        self.trace(scene, ray_bounce_depth, sampler, &mut 0)
+    }
+
+    /// Like `color`, adding the number of rays cast against the scene to
+    /// `rays`.
+    pub fn trace(
+        &self,
+        scene: &Scene,
+        ray_bounce_depth: usize,
+        sampler: &mut dyn Sampler,
+        rays: &mut u64,
+    ) -> Color {
+        if ray_bounce_depth == 0 {
             return Color::Black;
         }
 
-        if let Some(hit_record) = world.hit(self, 0.001, f64::INFINITY) {
-            if let Some((attenuation, scattered_ray)) =
-                hit_record.material.scatter(self, &hit_record)
+        *rays += 1;
+        if let Some(hit_record) = scene.hit(self, 0.001, f64::INFINITY) {
+            let sample = ScatterSample::draw(sampler);
+            let material = scene.material(hit_record.material);
+            let emitted = material.emitted(&hit_record).to_vec3d();
+            if let Some((attenuation, scattered_ray)) = material.scatter(self, &hit_record, &sample)
             {
                 return Color::RGB(
-                    attenuation.to_vec3d()
-                        * scattered_ray.color(world, ray_bounce_depth - 1).to_vec3d(),
+                    emitted
+                        + attenuation.to_vec3d()
+                            * scattered_ray
+                                .trace(scene, ray_bounce_depth - 1, sampler, rays)
+                                .to_vec3d(),
                 );
             } else {
-                return Color::Black;
+                return Color::RGB(emitted);
             }
         }
 
-        let unit_direction: Vec3D = self.direction.normalize();
-        let t = 0.5 * (unit_direction.y + 1.0);
-        let color_vector =
-            (1.0 - t) * Color::White.to_vec3d() + t * Color::new(0.5, 0.7, 1.0).to_vec3d();
-        Color::RGB(color_vector)
+        scene.background.color(self.direction)
     }
 }
@@ -0,0 +1,815 @@
+use std::{
+    fs, io,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use rayon::prelude::*;
+
+use crate::{
+    checkpoint::Checkpoint,
+    color::Color,
+    film::{Film, FilmTile, Filter},
+    progress::{Progress, ProgressCallback, Tracker},
+    random::hash,
+    sampler::Sampler,
+    scene::Scene,
+    tile::{tiles, Tile, TileOrder},
+    vec::Vec3D,
+};
+
+/// Keeps sampling only the pixels that are still noisy.
+#[derive(Debug, Clone, Copy)]
+pub struct Adaptive {
+    min_samples: usize,
+    max_samples: usize,
+    threshold: f64,
+    pass_samples: usize,
+}
+
+impl Adaptive {
+    /// Every pixel gets `min_samples` before its noise is judged, and is
+    /// done once the standard error of its mean brightness, relative to
+    /// that brightness, is at most `threshold` or it has `max_samples`.
+    pub fn new(min_samples: usize, max_samples: usize, threshold: f64) -> Self {
+        assert!(
+            min_samples <= max_samples,
+            "min_samples can't be more than max_samples"
+        );
+        assert!(
+            threshold >= 0.0 && threshold.is_finite(),
+            "threshold must be a non-negative number"
+        );
+        Self {
+            min_samples,
+            max_samples,
+            threshold,
+            pass_samples: min_samples.max(1),
+        }
+    }
+
+    /// Samples added to each unconverged pixel per pass, at least one.
+    pub fn with_pass_samples(mut self, pass_samples: usize) -> Self {
+        self.pass_samples = pass_samples.max(1);
+        self
+    }
+}
+
+/// Renders the whole image over and over, adding samples to every pixel,
+/// until a time budget or a number of samples per pixel is reached.
+/// Built with `for_time` or `for_samples`, so it always has one of them.
+#[derive(Debug, Clone)]
+pub struct Progressive {
+    pass_samples: usize,
+    target_samples: Option<usize>,
+    time_budget: Option<Duration>,
+    snapshots: Option<(PathBuf, Duration)>,
+    checkpoints: Option<(PathBuf, Duration)>,
+    resume: Option<PathBuf>,
+}
+
+impl Progressive {
+    /// Stops starting tiles once `budget` of wall-clock time has passed.
+    pub fn for_time(budget: Duration) -> Self {
+        Self {
+            pass_samples: 4,
+            target_samples: None,
+            time_budget: Some(budget),
+            snapshots: None,
+            checkpoints: None,
+            resume: None,
+        }
+    }
+
+    /// Stops once every pixel has `samples` samples.
+    pub fn for_samples(samples: usize) -> Self {
+        Self {
+            pass_samples: 4,
+            target_samples: Some(samples),
+            time_budget: None,
+            snapshots: None,
+            checkpoints: None,
+            resume: None,
+        }
+    }
+
+    pub fn with_time_budget(mut self, budget: Duration) -> Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    pub fn with_target_samples(mut self, samples: usize) -> Self {
+        self.target_samples = Some(samples);
+        self
+    }
+
+    /// Samples added to every pixel per pass over the image, at least one.
+    pub fn with_pass_samples(mut self, pass_samples: usize) -> Self {
+        self.pass_samples = pass_samples.max(1);
+        self
+    }
+
+    /// Writes a PPM of the image to `path` after the first pass that ends
+    /// at least `interval` after the last write.
+    pub fn with_snapshots(mut self, path: impl Into<PathBuf>, interval: Duration) -> Self {
+        self.snapshots = Some((path.into(), interval));
+        self
+    }
+
+    /// Saves the render's state to `path` like snapshots, and once more
+    /// at the end.
+    pub fn with_checkpoints(mut self, path: impl Into<PathBuf>, interval: Duration) -> Self {
+        self.checkpoints = Some((path.into(), interval));
+        self
+    }
+
+    /// Starts from the samples in a checkpoint instead of a blank image.
+    /// The scene, sampler and renderer must match those it was saved with.
+    pub fn with_resume(mut self, path: impl Into<PathBuf>) -> Self {
+        self.resume = Some(path.into());
+        self
+    }
+}
+
+fn invalid(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+// Brightness below which the relative error is measured against this
+// instead, so near-black pixels don't sample forever
+const DARK: f64 = 0.1;
+
+/// Count and luminance variance of one pixel's samples.
+#[derive(Debug, Clone, Copy)]
+struct PixelStats {
+    samples: usize,
+    // Welford's running mean and sum of squared differences
+    mean: f64,
+    m2: f64,
+}
+
+impl PixelStats {
+    fn new() -> Self {
+        Self {
+            samples: 0,
+            mean: 0.0,
+            m2: 0.0,
+        }
+    }
+
+    fn add(&mut self, color: Vec3D) {
+        self.samples += 1;
+        let luminance = Color::RGB(color).luminance();
+        let delta = luminance - self.mean;
+        self.mean += delta / self.samples as f64;
+        self.m2 += delta * (luminance - self.mean);
+    }
+
+    fn converged(&self, threshold: f64) -> bool {
+        if self.samples < 2 {
+            return false;
+        }
+        let variance = self.m2 / (self.samples - 1) as f64;
+        let standard_error = (variance / self.samples as f64).sqrt();
+        standard_error <= threshold * self.mean.max(DARK)
+    }
+}
+
+/// Traces a scene into a `Frame`, a fixed number of samples per pixel or
+/// adaptively.
+pub struct Renderer {
+    width: usize,
+    height: usize,
+    samples_per_pixel: usize,
+    max_depth: usize,
+    adaptive: Option<Adaptive>,
+    filter: Filter,
+    tile_size: usize,
+    tile_order: TileOrder,
+    progress: Option<Arc<ProgressCallback>>,
+}
+
+impl Renderer {
+    pub fn new(width: usize, height: usize, samples_per_pixel: usize) -> Self {
+        Self {
+            width,
+            height,
+            samples_per_pixel,
+            max_depth: 50,
+            adaptive: None,
+            filter: Filter::default(),
+            tile_size: 32,
+            tile_order: TileOrder::default(),
+            progress: None,
+        }
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Renders in passes, stopping per pixel instead of at a fixed count.
+    pub fn with_adaptive(mut self, adaptive: Adaptive) -> Self {
+        self.adaptive = Some(adaptive);
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Tiles of at most `size` pixels square are the unit of work handed
+    /// to threads.
+    pub fn with_tile_size(mut self, size: usize) -> Self {
+        self.tile_size = size;
+        self
+    }
+
+    pub fn with_tile_order(mut self, order: TileOrder) -> Self {
+        self.tile_order = order;
+        self
+    }
+
+    /// Calls `callback` each time a tile finishes, from whichever thread
+    /// rendered it.
+    pub fn with_progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&Progress) + Send + Sync + 'static,
+    {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Tiles are rendered on rayon's pool, all adding into one shared
+    /// film. Pixels only depend on their own samples and the film sums in
+    /// fixed point, so the result is the same for any number of threads.
+    pub fn render<S: Sampler + Clone>(&self, scene: &Scene, sampler: &S) -> Frame {
+        let film = Film::new(self.width, self.height, self.filter);
+        let mut tiles = self.tiles();
+
+        let tracker = Tracker::new(
+            tiles.len(),
+            self.width * self.height,
+            None,
+            self.progress.as_deref(),
+        );
+        tiles.par_iter_mut().for_each(|(tile, pixels)| {
+            let rays = self.render_tile(scene, &mut sampler.clone(), &film, tile, pixels);
+            let samples = pixels.iter().map(|pixel| pixel.samples as u64).sum();
+            tracker.tile_done(tile.pixel_count(), samples, rays);
+        });
+
+        self.frame(film, &tiles)
+    }
+
+    /// Renders in passes over the whole image, ignoring the samples per
+    /// pixel and adaptive settings. Pixels take the same samples as with
+    /// `render`, so running to a sample count gives the same image up to
+    /// rounding. Fails if a snapshot or checkpoint can't be written, or
+    /// the checkpoint to resume from is of another scene or setup.
+    pub fn render_progressive<S: Sampler + Clone>(
+        &self,
+        scene: &Scene,
+        sampler: &S,
+        progressive: &Progressive,
+    ) -> io::Result<Frame> {
+        let film = Film::new(self.width, self.height, self.filter);
+        let mut tiles = self.tiles();
+
+        let settings = self.settings_hash();
+        let scene_hash = scene.content_hash();
+        let kind: Vec<u64> = sampler.kind().bytes().map(u64::from).collect();
+        let sampler_hash = hash(&[
+            hash(&kind),
+            sampler.seed(),
+            sampler.samples_per_pixel() as u64,
+        ]);
+
+        let mut previous = Duration::ZERO;
+        if let Some(path) = &progressive.resume {
+            let checkpoint = Checkpoint::load(path)?;
+            if (checkpoint.width, checkpoint.height) != (self.width, self.height)
+                || checkpoint.settings != settings
+            {
+                return Err(invalid("checkpoint was made with other render settings"));
+            }
+            if checkpoint.scene != scene_hash {
+                return Err(invalid("checkpoint is of a different scene"));
+            }
+            if checkpoint.sampler != sampler_hash {
+                return Err(invalid("checkpoint was made with another sampler"));
+            }
+            film.set_sums(&checkpoint.film);
+            for (tile, pixels) in &mut tiles {
+                for ((x, y), pixel) in tile.pixels().zip(pixels.iter_mut()) {
+                    let (samples, mean, m2) = checkpoint.stats[y * self.width + x];
+                    *pixel = PixelStats {
+                        samples: samples as usize,
+                        mean,
+                        m2,
+                    };
+                }
+            }
+            previous = checkpoint.elapsed;
+        }
+        let checkpoint = |tiles: &[(Tile, Vec<PixelStats>)], elapsed| Checkpoint {
+            width: self.width,
+            height: self.height,
+            settings,
+            scene: scene_hash,
+            sampler: sampler_hash,
+            elapsed,
+            film: film.sums(),
+            stats: self
+                .gather(tiles)
+                .iter()
+                .map(|pixel| (pixel.samples as u64, pixel.mean, pixel.m2))
+                .collect(),
+        };
+
+        // Pixels may be uneven after a render stopped partway through a
+        // pass, and the next pass evens them out
+        let mut samples_done = tiles
+            .iter()
+            .flat_map(|(_, pixels)| pixels.iter().map(|pixel| pixel.samples))
+            .min()
+            .unwrap_or(0);
+        let pass_samples = progressive.pass_samples;
+        let passes = progressive.target_samples.map_or(0, |target| {
+            target.saturating_sub(samples_done).div_ceil(pass_samples)
+        });
+        // The budget covers the time spent before resuming too
+        let budget = progressive
+            .time_budget
+            .map(|budget| budget.saturating_sub(previous));
+        let tracker = Tracker::new(
+            passes * tiles.len(),
+            passes * self.width * self.height,
+            budget,
+            self.progress.as_deref(),
+        );
+        let out_of_time = || budget.is_some_and(|budget| tracker.elapsed() >= budget);
+
+        let (mut last_snapshot, mut last_checkpoint) = (Instant::now(), Instant::now());
+        while progressive
+            .target_samples
+            .is_none_or(|target| samples_done < target)
+            && !out_of_time()
+        {
+            let count = progressive.target_samples.map_or(pass_samples, |target| {
+                pass_samples.min(target - samples_done)
+            });
+            let end = samples_done + count;
+            tiles.par_iter_mut().for_each(|(tile, pixels)| {
+                if out_of_time() {
+                    return;
+                }
+                let before: usize = pixels.iter().map(|pixel| pixel.samples).sum();
+                let mut buffer = film.tile(tile);
+                let sampler = &mut sampler.clone();
+                let active = |pixel: &PixelStats| pixel.samples < end;
+                let rays = self.pass(
+                    scene,
+                    sampler,
+                    &mut buffer,
+                    tile,
+                    pixels,
+                    count,
+                    end,
+                    active,
+                );
+                film.merge(buffer);
+                let after: usize = pixels.iter().map(|pixel| pixel.samples).sum();
+                tracker.tile_done(tile.pixel_count(), (after - before) as u64, rays);
+            });
+            samples_done = end;
+
+            if let Some((path, interval)) = &progressive.snapshots {
+                if last_snapshot.elapsed() >= *interval {
+                    fs::write(path, film.to_ppm())?;
+                    last_snapshot = Instant::now();
+                }
+            }
+            if let Some((path, interval)) = &progressive.checkpoints {
+                if last_checkpoint.elapsed() >= *interval {
+                    checkpoint(&tiles, previous + tracker.elapsed()).save(path)?;
+                    last_checkpoint = Instant::now();
+                }
+            }
+        }
+        // Always checkpoint the end, so a finished render can be resumed
+        // with a larger budget
+        if let Some((path, _)) = &progressive.checkpoints {
+            checkpoint(&tiles, previous + tracker.elapsed()).save(path)?;
+        }
+
+        Ok(self.frame(film, &tiles))
+    }
+
+    fn tiles(&self) -> Vec<(Tile, Vec<PixelStats>)> {
+        tiles(self.width, self.height, self.tile_size, self.tile_order)
+            .into_iter()
+            .map(|tile| (tile, vec![PixelStats::new(); tile.pixel_count()]))
+            .collect()
+    }
+
+    // Collects the tiles' pixel statistics into rows
+    fn gather(&self, tiles: &[(Tile, Vec<PixelStats>)]) -> Vec<PixelStats> {
+        let mut stats = vec![PixelStats::new(); self.width * self.height];
+        for (tile, pixels) in tiles {
+            for ((x, y), pixel) in tile.pixels().zip(pixels) {
+                stats[y * self.width + x] = *pixel;
+            }
+        }
+        stats
+    }
+
+    fn frame(&self, film: Film, tiles: &[(Tile, Vec<PixelStats>)]) -> Frame {
+        Frame {
+            pixels: self.gather(tiles),
+            film,
+        }
+    }
+
+    // Settings a checkpoint's sums are only valid for
+    fn settings_hash(&self) -> u64 {
+        let filter: Vec<u64> = format!("{:?}", self.filter)
+            .bytes()
+            .map(u64::from)
+            .collect();
+        hash(&[
+            self.width as u64,
+            self.height as u64,
+            self.max_depth as u64,
+            hash(&filter),
+        ])
+    }
+
+    // Splats into a buffer of its own, added to the shared film at the end.
+    // Returns the number of rays cast.
+    fn render_tile<S: Sampler>(
+        &self,
+        scene: &Scene,
+        sampler: &mut S,
+        film: &Film,
+        tile: &Tile,
+        pixels: &mut [PixelStats],
+    ) -> u64 {
+        let mut buffer = film.tile(tile);
+        let mut rays = 0;
+        match self.adaptive {
+            None => {
+                let count = self.samples_per_pixel;
+                rays += self.pass(
+                    scene,
+                    sampler,
+                    &mut buffer,
+                    tile,
+                    pixels,
+                    count,
+                    count,
+                    |_| true,
+                );
+            }
+            Some(adaptive) => {
+                let (count, max) = (adaptive.min_samples, adaptive.max_samples);
+                rays += self.pass(
+                    scene,
+                    sampler,
+                    &mut buffer,
+                    tile,
+                    pixels,
+                    count,
+                    max,
+                    |_| true,
+                );
+                let active = |pixel: &PixelStats| {
+                    pixel.samples < adaptive.max_samples && !pixel.converged(adaptive.threshold)
+                };
+                while pixels.iter().any(active) {
+                    let count = adaptive.pass_samples;
+                    rays += self.pass(
+                        scene,
+                        sampler,
+                        &mut buffer,
+                        tile,
+                        pixels,
+                        count,
+                        max,
+                        active,
+                    );
+                }
+            }
+        }
+        film.merge(buffer);
+        rays
+    }
+
+    // Adds up to `count` samples to every pixel of the tile `active` picks,
+    // stopping at `limit` samples, and returns the rays cast
+    #[allow(clippy::too_many_arguments)]
+    fn pass<S, F>(
+        &self,
+        scene: &Scene,
+        sampler: &mut S,
+        film: &mut FilmTile,
+        tile: &Tile,
+        pixels: &mut [PixelStats],
+        count: usize,
+        limit: usize,
+        active: F,
+    ) -> u64
+    where
+        S: Sampler,
+        F: Fn(&PixelStats) -> bool,
+    {
+        let mut rays = 0;
+        for ((x, y), pixel) in tile.pixels().zip(pixels) {
+            if !active(pixel) {
+                continue;
+            }
+            let end = (pixel.samples + count).min(limit);
+            for sample in pixel.samples..end {
+                sampler.start_pixel_sample((x, y), sample);
+                let (du, dv) = sampler.get_2d();
+                let (fx, fy) = (x as f64 + du, y as f64 + dv);
+                let (u, v) = (fx / self.width as f64, fy / self.height as f64);
+                // Outside what the camera sees the image stays black
+                let color = match scene.camera.get_weighted_ray(u, v, sampler) {
+                    Some((ray, weight)) => {
+                        ray.trace(scene, self.max_depth, sampler, &mut rays)
+                            .to_vec3d()
+                            * weight
+                    }
+                    None => Color::Black.to_vec3d(),
+                } * scene.camera.exposure();
+                pixel.add(color);
+                film.add_sample((fx, fy), color);
+            }
+        }
+        rays
+    }
+}
+
+/// Rendered pixels, with `y` running up from the bottom row as in camera
+/// coordinates.
+pub struct Frame {
+    pixels: Vec<PixelStats>,
+    film: Film,
+}
+
+impl Frame {
+    pub fn width(&self) -> usize {
+        self.film.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.film.height()
+    }
+
+    /// The filtered pixel color.
+    pub fn color(&self, x: usize, y: usize) -> Color {
+        self.film.color(x, y)
+    }
+
+    pub fn film(&self) -> &Film {
+        &self.film
+    }
+
+    /// Samples taken inside the pixel.
+    pub fn samples(&self, x: usize, y: usize) -> usize {
+        self.pixels[y * self.width() + x].samples
+    }
+
+    /// Samples each pixel took, from blue for the fewest through green to
+    /// red for the most, in the same layout as the frame.
+    pub fn heatmap(&self) -> Vec<Color> {
+        let counts = self.pixels.iter().map(|pixel| pixel.samples);
+        let (min, max) = (counts.clone().min().unwrap_or(0), counts.max().unwrap_or(0));
+        self.pixels
+            .iter()
+            .map(|pixel| {
+                let t = if max > min {
+                    (pixel.samples - min) as f64 / (max - min) as f64
+                } else {
+                    0.0
+                };
+                if t < 0.5 {
+                    Color::new(0.0, 2.0 * t, 1.0 - 2.0 * t)
+                } else {
+                    Color::new(2.0 * t - 1.0, 2.0 - 2.0 * t, 0.0)
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        camera::{FieldOfView, PerspectiveCamera},
+        material::Lambertian,
+        object::Sphere,
+        sampler::SobolSampler,
+        scene::Background,
+        vec::Point3D,
+    };
+
+    #[test]
+    fn test_adaptive_skips_flat_pixels() {
+        let mut builder = Scene::builder();
+        let material = builder.add_material(Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+        builder.add(Sphere::new(Point3D::new(0.0, 0.0, -3.0), 1.0, material));
+        builder.add(Sphere::new(
+            Point3D::new(0.0, -101.0, -3.0),
+            100.0,
+            material,
+        ));
+        builder.set_background(Background::Solid(Color::White));
+        builder.set_camera(PerspectiveCamera::new(
+            Point3D::new(0.0, 0.0, 0.0),
+            Point3D::new(0.0, 0.0, -1.0),
+            Vec3D::new(0.0, 1.0, 0.0),
+            FieldOfView::Vertical(90.0),
+            1.0,
+            0.0,
+            1.0,
+        ));
+        let scene = builder.build();
+        let sampler = SobolSampler::new(64, 1);
+
+        let frame = Renderer::new(9, 9, 4).render(&scene, &sampler);
+        assert_eq!(frame.samples(0, 8), 4);
+        assert_eq!(frame.samples(4, 4), 4);
+
+        let adaptive = Adaptive::new(8, 64, 0.01).with_pass_samples(8);
+        let frame = Renderer::new(9, 9, 4)
+            .with_adaptive(adaptive)
+            .render(&scene, &sampler);
+        // The top corner only sees the background, the center a diffuse
+        // sphere lit partly by the sky and partly by the ground
+        assert_eq!(frame.samples(0, 8), 8);
+        assert_eq!(frame.color(0, 8).to_vec3d(), Color::White.to_vec3d());
+        assert_eq!(frame.samples(4, 4), 64);
+
+        let heatmap = frame.heatmap();
+        assert_eq!(heatmap.len(), 81);
+        assert_eq!(heatmap[8 * 9].to_vec3d(), Color::Blue.to_vec3d());
+        assert_eq!(heatmap[4 * 9 + 4].to_vec3d(), Color::Red.to_vec3d());
+    }
+
+    #[test]
+    #[should_panic(expected = "min_samples")]
+    fn test_adaptive_rejects_inverted_range() {
+        Adaptive::new(16, 8, 0.01);
+    }
+
+    #[test]
+    fn test_tiles_ignore_thread_count() {
+        let mut builder = Scene::builder();
+        let material = builder.add_material(Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+        builder.add(Sphere::new(Point3D::new(0.0, 0.0, -2.0), 1.0, material));
+        let scene = builder.build();
+
+        let renderer = Renderer::new(20, 12, 4)
+            .with_filter(Filter::mitchell(2.0))
+            .with_tile_size(4)
+            .with_tile_order(TileOrder::Spiral);
+        let render = |threads| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let frame = pool.install(|| renderer.render(&scene, &SobolSampler::new(4, 3)));
+            (0..12)
+                .flat_map(|y| (0..20).map(move |x| (x, y)))
+                .map(|(x, y)| (frame.color(x, y).to_vec3d(), frame.samples(x, y)))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(render(1), render(3));
+    }
+
+    #[test]
+    fn test_progressive_matches_fixed_render() {
+        let mut builder = Scene::builder();
+        let material = builder.add_material(Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+        builder.add(Sphere::new(Point3D::new(0.0, 0.0, -2.0), 1.0, material));
+        let scene = builder.build();
+        let sampler = SobolSampler::new(8, 5);
+        let renderer = Renderer::new(8, 6, 8).with_tile_size(4);
+
+        let fixed = renderer.render(&scene, &sampler);
+        let path = std::env::temp_dir().join("test_progressive_matches_fixed_render.ppm");
+        let progressive = Progressive::for_samples(8)
+            .with_pass_samples(3)
+            .with_snapshots(&path, Duration::ZERO);
+        let frame = renderer
+            .render_progressive(&scene, &sampler, &progressive)
+            .unwrap();
+        for y in 0..6 {
+            for x in 0..8 {
+                assert_eq!(frame.samples(x, y), 8);
+                let error = frame.color(x, y).to_vec3d() - fixed.color(x, y).to_vec3d();
+                assert!(error.length() < 1.0e-6);
+            }
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), frame.film().to_ppm());
+        fs::remove_file(path).unwrap();
+
+        // Out of time before the first pass
+        let frame = renderer
+            .render_progressive(&scene, &sampler, &Progressive::for_time(Duration::ZERO))
+            .unwrap();
+        assert_eq!(frame.samples(3, 3), 0);
+    }
+
+    #[test]
+    fn test_progressive_ignores_adaptive_maximum() {
+        let scene = Scene::builder().build();
+        let renderer = Renderer::new(4, 4, 2).with_adaptive(Adaptive::new(2, 4, 0.01));
+        let frame = renderer
+            .render_progressive(
+                &scene,
+                &SobolSampler::new(2, 0),
+                &Progressive::for_samples(10).with_pass_samples(3),
+            )
+            .unwrap();
+        assert_eq!(frame.samples(0, 0), 10);
+        assert_eq!(frame.samples(3, 3), 10);
+    }
+
+    #[test]
+    fn test_resume_continues_checkpoint() {
+        let scene = |radius| {
+            let mut builder = Scene::builder();
+            let material =
+                builder.add_material(Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+            builder.add(Sphere::new(Point3D::new(0.0, 0.0, -2.0), radius, material));
+            builder.build()
+        };
+        let sampler = SobolSampler::new(8, 2);
+        let renderer = Renderer::new(8, 6, 8)
+            .with_tile_size(4)
+            .with_adaptive(Adaptive::new(2, 4, 0.5));
+        let path = std::env::temp_dir().join("test_resume_continues_checkpoint.bin");
+
+        let first = Progressive::for_samples(4).with_checkpoints(&path, Duration::from_secs(3600));
+        renderer
+            .render_progressive(&scene(1.0), &sampler, &first)
+            .unwrap();
+        let resumed = Progressive::for_samples(8).with_resume(&path);
+        let frame = renderer
+            .render_progressive(&scene(1.0), &sampler, &resumed)
+            .unwrap();
+        let whole = renderer
+            .render_progressive(&scene(1.0), &sampler, &Progressive::for_samples(8))
+            .unwrap();
+        for y in 0..6 {
+            for x in 0..8 {
+                assert_eq!(frame.samples(x, y), 8);
+                let error = frame.color(x, y).to_vec3d() - whole.color(x, y).to_vec3d();
+                assert!(error.length() < 1.0e-8);
+            }
+        }
+
+        let error = renderer
+            .render_progressive(&scene(0.9), &sampler, &resumed)
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(renderer
+            .render_progressive(&scene(1.0), &SobolSampler::new(8, 3), &resumed)
+            .is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_progress_reaches_every_tile() {
+        let scene = Scene::builder().build();
+        let updates = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = updates.clone();
+        Renderer::new(10, 6, 3)
+            .with_tile_size(4)
+            .with_progress(move |progress| recorded.lock().unwrap().push(*progress))
+            .render(&scene, &SobolSampler::new(3, 0));
+
+        let updates = updates.lock().unwrap();
+        assert_eq!(updates.len(), 3 * 2);
+        for (i, progress) in updates.iter().enumerate() {
+            assert_eq!(progress.tiles_done, i + 1);
+        }
+        let last = updates.last().unwrap();
+        assert!(last.is_done());
+        assert_eq!(last.pixels_done, 60);
+        assert_eq!(last.samples, 60 * 3);
+        // Every camera ray misses the empty scene
+        assert_eq!(last.rays, 60 * 3);
+        assert_eq!(last.eta(), Some(std::time::Duration::ZERO));
+    }
+}
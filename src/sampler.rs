@@ -0,0 +1,513 @@
+use rand::Rng;
+
+use crate::random::{hash, sample_rng, SampleRng};
+
+/// Source of the uniform numbers in `[0, 1)` that make up each sample of a
+/// pixel.
+///
+/// Within a sample, dimensions are requested in a fixed order: the offset
+/// inside the pixel (2D), the lens position (2D) and the shutter time (1D),
+/// then one `ScatterSample` per bounce. Keeping to that order is what lets
+/// the stratified and low-discrepancy samplers spread every decision
+/// evenly over a pixel's samples.
+pub trait Sampler: Send + Sync {
+    /// Name of the kind of sequence, the same in every build, for telling
+    /// the samplers of saved renders apart.
+    fn kind(&self) -> &'static str;
+
+    fn samples_per_pixel(&self) -> usize;
+
+    /// Seed the sequences are scrambled with. With the sample index, it is
+    /// all a sampler needs to pick up where it left off.
+    fn seed(&self) -> u64;
+
+    /// Starts over at the first dimension of the given sample of a pixel.
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample: usize);
+
+    fn get_1d(&mut self) -> f64;
+
+    fn get_2d(&mut self) -> (f64, f64);
+}
+
+// Where a sampler is in the sample it is generating
+#[derive(Debug, Clone, Copy)]
+struct Cursor {
+    seed: u64,
+    pixel: (u64, u64),
+    sample: u64,
+    dimension: u64,
+}
+
+impl Cursor {
+    fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            pixel: (0, 0),
+            sample: 0,
+            dimension: 0,
+        }
+    }
+
+    fn start(&mut self, pixel: (usize, usize), sample: usize) {
+        self.pixel = (pixel.0 as u64, pixel.1 as u64);
+        self.sample = sample as u64;
+        self.dimension = 0;
+    }
+
+    // Hash of the pixel and the current dimension, which stays the same
+    // for every sample of the pixel, before moving to the next dimension
+    fn advance(&mut self) -> u64 {
+        let h = hash(&[self.seed, self.pixel.0, self.pixel.1, self.dimension]);
+        self.dimension += 1;
+        h
+    }
+}
+
+fn to_unit(bits: u64) -> f64 {
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Independent uniform random numbers, seeded per pixel and sample.
+#[derive(Clone)]
+pub struct IndependentSampler {
+    samples_per_pixel: usize,
+    seed: u64,
+    rng: SampleRng,
+}
+
+impl IndependentSampler {
+    pub fn new(samples_per_pixel: usize, seed: u64) -> Self {
+        Self {
+            samples_per_pixel,
+            seed,
+            rng: sample_rng(seed, 0, 0),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn kind(&self) -> &'static str {
+        "independent"
+    }
+
+    fn samples_per_pixel(&self) -> usize {
+        self.samples_per_pixel
+    }
+
+    fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    fn start_pixel_sample(&mut self, (x, y): (usize, usize), sample: usize) {
+        let pixel = (y as u64) << 32 | x as u64;
+        self.rng = sample_rng(self.seed, pixel, sample as u64);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.rng.gen()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.rng.gen(), self.rng.gen())
+    }
+}
+
+/// Jittered strata, one per sample, visited in a different random order
+/// for each dimension so that dimensions don't correlate.
+#[derive(Clone)]
+pub struct StratifiedSampler {
+    x_samples: usize,
+    y_samples: usize,
+    cursor: Cursor,
+}
+
+impl StratifiedSampler {
+    /// `x_samples` by `y_samples` strata for 2D dimensions, and their
+    /// product for 1D ones.
+    pub fn new(x_samples: usize, y_samples: usize, seed: u64) -> Self {
+        assert!(
+            x_samples > 0 && y_samples > 0,
+            "stratified sampler needs at least one stratum on each axis"
+        );
+        Self {
+            x_samples,
+            y_samples,
+            cursor: Cursor::new(seed),
+        }
+    }
+
+    // Stratum for the current sample, wrapping around past the last one
+    fn stratum(&self, h: u64) -> usize {
+        let count = self.samples_per_pixel() as u32;
+        permutation_element(self.cursor.sample as u32 % count, count, h as u32) as usize
+    }
+
+    fn jitter(&self, h: u64, axis: u64) -> f64 {
+        to_unit(hash(&[h, self.cursor.sample, axis]))
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn kind(&self) -> &'static str {
+        "stratified"
+    }
+
+    fn samples_per_pixel(&self) -> usize {
+        self.x_samples * self.y_samples
+    }
+
+    fn seed(&self) -> u64 {
+        self.cursor.seed
+    }
+
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample: usize) {
+        self.cursor.start(pixel, sample);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let h = self.cursor.advance();
+        let stratum = self.stratum(h);
+        (stratum as f64 + self.jitter(h, 0)) / self.samples_per_pixel() as f64
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let h = self.cursor.advance();
+        let stratum = self.stratum(h);
+        let (x, y) = (stratum % self.x_samples, stratum / self.x_samples);
+        (
+            (x as f64 + self.jitter(h, 0)) / self.x_samples as f64,
+            (y as f64 + self.jitter(h, 1)) / self.y_samples as f64,
+        )
+    }
+}
+
+// Kensler's hashed permutation: element `i` of a random permutation of
+// `0..count` picked by `seed`, without storing the permutation
+fn permutation_element(mut i: u32, count: u32, seed: u32) -> u32 {
+    let mut w = count - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        // Walk the cycle until we land back inside `0..count`
+        if i < count {
+            return (i.wrapping_add(seed)) % count;
+        }
+    }
+}
+
+const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+/// Halton sequence with a prime base per dimension, its digits randomly
+/// permuted per pixel. Dimensions past the last base fall back to
+/// independent numbers.
+#[derive(Clone)]
+pub struct HaltonSampler {
+    samples_per_pixel: usize,
+    cursor: Cursor,
+}
+
+impl HaltonSampler {
+    pub fn new(samples_per_pixel: usize, seed: u64) -> Self {
+        Self {
+            samples_per_pixel,
+            cursor: Cursor::new(seed),
+        }
+    }
+
+    fn next(&mut self) -> f64 {
+        let dimension = self.cursor.dimension as usize;
+        let h = self.cursor.advance();
+        match PRIMES.get(dimension) {
+            Some(&base) => scrambled_radical_inverse(base, self.cursor.sample, h),
+            None => to_unit(hash(&[h, self.cursor.sample])),
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn kind(&self) -> &'static str {
+        "halton"
+    }
+
+    fn samples_per_pixel(&self) -> usize {
+        self.samples_per_pixel
+    }
+
+    fn seed(&self) -> u64 {
+        self.cursor.seed
+    }
+
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample: usize) {
+        self.cursor.start(pixel, sample);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        self.next()
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        (self.next(), self.next())
+    }
+}
+
+// Mirrors the digits of `index` in `base` around the radix point, passing
+// each through a permutation picked by `seed` and the digit's position.
+// Permuting keeps the points stratified where a random shift would not.
+fn scrambled_radical_inverse(base: u64, mut index: u64, seed: u64) -> f64 {
+    let inv_base = 1.0 / base as f64;
+    let (mut value, mut inv_base_m, mut position) = (0.0, 1.0, 0);
+    // The zeros past the last digit are permuted too, so keep going until
+    // digits no longer change the result
+    while 1.0 - (base - 1) as f64 * inv_base_m < 1.0 {
+        let next = index / base;
+        let digit = (index - next * base) as u32;
+        let digit = permutation_element(digit, base as u32, hash(&[seed, position]) as u32);
+        inv_base_m *= inv_base;
+        value += digit as f64 * inv_base_m;
+        index = next;
+        position += 1;
+    }
+    value.min(1.0 - f64::EPSILON)
+}
+
+/// The first two Sobol dimensions, Owen-scrambled with a different hash for
+/// every pixel and dimension and with the sample order shuffled the same
+/// way (Burley, "Practical Hash-based Owen Scrambling"). Works best with a
+/// power of two samples per pixel.
+#[derive(Clone)]
+pub struct SobolSampler {
+    samples_per_pixel: usize,
+    cursor: Cursor,
+}
+
+impl SobolSampler {
+    pub fn new(samples_per_pixel: usize, seed: u64) -> Self {
+        Self {
+            samples_per_pixel,
+            cursor: Cursor::new(seed),
+        }
+    }
+
+    // Shuffled index and the dimension's hash
+    fn index(&mut self) -> (u32, u64) {
+        let h = self.cursor.advance();
+        (
+            nested_uniform_scramble(self.cursor.sample as u32, h as u32),
+            h,
+        )
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn kind(&self) -> &'static str {
+        "sobol"
+    }
+
+    fn samples_per_pixel(&self) -> usize {
+        self.samples_per_pixel
+    }
+
+    fn seed(&self) -> u64 {
+        self.cursor.seed
+    }
+
+    fn start_pixel_sample(&mut self, pixel: (usize, usize), sample: usize) {
+        self.cursor.start(pixel, sample);
+    }
+
+    fn get_1d(&mut self) -> f64 {
+        let (index, h) = self.index();
+        let x = nested_uniform_scramble(sobol(index, 0), hash(&[h, 0]) as u32);
+        x as f64 / 2f64.powi(32)
+    }
+
+    fn get_2d(&mut self) -> (f64, f64) {
+        let (index, h) = self.index();
+        let x = nested_uniform_scramble(sobol(index, 0), hash(&[h, 0]) as u32);
+        let y = nested_uniform_scramble(sobol(index, 1), hash(&[h, 1]) as u32);
+        (x as f64 / 2f64.powi(32), y as f64 / 2f64.powi(32))
+    }
+}
+
+// Sobol dimension 0 (van der Corput) or 1, as a fraction of 2^32
+fn sobol(index: u32, dimension: usize) -> u32 {
+    if dimension == 0 {
+        return index.reverse_bits();
+    }
+    let (mut x, mut v, mut index) = (0, 1 << 31, index);
+    while index != 0 {
+        if index & 1 != 0 {
+            x ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    x
+}
+
+// Each bit is flipped depending only on the bits below it, which after
+// reversing makes an Owen scramble
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x ^= x.wrapping_mul(0x3d20adea);
+    x = x.wrapping_add(seed);
+    x = x.wrapping_mul((seed >> 16) | 1);
+    x ^= x.wrapping_mul(0x05526c56);
+    x ^= x.wrapping_mul(0x53a22864);
+    x
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        color::Color,
+        material::{Dielectric, Lambertian},
+        object::Sphere,
+        scene::Scene,
+        vec::{Point3D, Vec3D},
+    };
+    use rayon::prelude::*;
+
+    // Which of `n` by `m` cells each of a pixel's 2D samples falls in
+    fn cells(sampler: &mut dyn Sampler, skip: usize, n: usize, m: usize) -> Vec<usize> {
+        let mut cells: Vec<usize> = (0..sampler.samples_per_pixel())
+            .map(|sample| {
+                sampler.start_pixel_sample((3, 5), sample);
+                for _ in 0..skip {
+                    sampler.get_2d();
+                }
+                let (u, v) = sampler.get_2d();
+                assert!((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v));
+                (v * m as f64) as usize * n + (u * n as f64) as usize
+            })
+            .collect();
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn test_samplers_stratify() {
+        let all: Vec<usize> = (0..16).collect();
+        for skip in [0, 3] {
+            let mut stratified = StratifiedSampler::new(4, 4, 1);
+            assert_eq!(cells(&mut stratified, skip, 4, 4), all);
+            let mut sobol = SobolSampler::new(16, 1);
+            assert_eq!(cells(&mut sobol, skip, 4, 4), all);
+            assert_eq!(cells(&mut sobol, skip, 16, 1), all);
+            assert_eq!(cells(&mut sobol, skip, 2, 8), all);
+        }
+        // Bases 2 and 3 in the first two dimensions
+        let mut halton = HaltonSampler::new(6, 1);
+        assert_eq!(cells(&mut halton, 0, 2, 3), (0..6).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_permutation_element_is_a_permutation() {
+        for count in [1, 5, 16, 100] {
+            let mut elements: Vec<u32> = (0..count)
+                .map(|i| permutation_element(i, count, 0xdead_beef))
+                .collect();
+            elements.sort();
+            assert_eq!(elements, (0..count).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one stratum")]
+    fn test_stratified_rejects_zero_samples() {
+        StratifiedSampler::new(4, 0, 1);
+    }
+
+    #[test]
+    fn test_low_discrepancy_beats_independent() {
+        // Squared error estimating the area of a quarter disk, over many pixels
+        let error = |sampler: &mut dyn Sampler| {
+            let samples = sampler.samples_per_pixel();
+            (0..64)
+                .map(|pixel| {
+                    let inside = (0..samples)
+                        .filter(|&sample| {
+                            sampler.start_pixel_sample((pixel, 0), sample);
+                            let (u, v) = sampler.get_2d();
+                            u * u + v * v < 1.0
+                        })
+                        .count();
+                    (inside as f64 / samples as f64 - std::f64::consts::FRAC_PI_4).powi(2)
+                })
+                .sum::<f64>()
+        };
+        let independent = error(&mut IndependentSampler::new(64, 1));
+        assert!(error(&mut StratifiedSampler::new(8, 8, 1)) < independent / 4.0);
+        assert!(error(&mut HaltonSampler::new(64, 1)) < independent / 4.0);
+        assert!(error(&mut SobolSampler::new(64, 1)) < independent / 4.0);
+    }
+
+    #[test]
+    fn test_render_ignores_thread_count() {
+        let mut builder = Scene::builder();
+        let diffuse = builder.add_material(Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+        let glass = builder.add_material(Arc::new(Dielectric::new(1.5)));
+        builder.add(Sphere::new(Point3D::new(0.0, -100.5, -1.0), 100.0, diffuse));
+        builder.add(Sphere::new(Point3D::new(0.0, 0.0, -1.0), 0.5, glass));
+        let scene = builder.build();
+
+        let (width, height) = (8, 8);
+        let sampler = SobolSampler::new(4, 7);
+        let render = |threads| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| {
+                (0..width * height)
+                    .into_par_iter()
+                    .map(|pixel| {
+                        let (x, y) = (pixel % width, pixel / width);
+                        let mut sampler = sampler.clone();
+                        (0..sampler.samples_per_pixel())
+                            .map(|sample| {
+                                sampler.start_pixel_sample((x, y), sample);
+                                let (du, dv) = sampler.get_2d();
+                                let u = (x as f64 + du) / width as f64;
+                                let v = (y as f64 + dv) / height as f64;
+                                let ray = scene.camera.get_ray(u, v, &mut sampler).unwrap();
+                                ray.color(&scene, 10, &mut sampler).to_vec3d()
+                            })
+                            .fold(Vec3D::new(0.0, 0.0, 0.0), |a, b| a + b)
+                    })
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert_eq!(render(1), render(4));
+    }
+}
@@ -0,0 +1,334 @@
+use std::sync::Arc;
+
+use crate::{
+    bvh::{Aabb, Bvh},
+    camera::{Camera, FieldOfView, PerspectiveCamera},
+    color::Color,
+    content::HashInto,
+    hit::{Hit, HitRecord},
+    material::{MaterialId, Scatter},
+    random::hash,
+    ray::Ray,
+    vec::{Point3D, Vec3D},
+};
+
+/// What rays that leave the scene see.
+#[derive(Clone, Copy)]
+pub enum Background {
+    Solid(Color),
+    /// Blend from `bottom` straight down to `top` straight up.
+    Gradient {
+        bottom: Color,
+        top: Color,
+    },
+}
+
+impl Background {
+    pub fn color(&self, direction: Vec3D) -> Color {
+        match self {
+            Background::Solid(color) => *color,
+            Background::Gradient { bottom, top } => {
+                let t = 0.5 * (direction.normalize().y + 1.0);
+                Color::RGB((1.0 - t) * bottom.to_vec3d() + t * top.to_vec3d())
+            }
+        }
+    }
+}
+
+impl HashInto for Background {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        match self {
+            Background::Solid(color) => ("solid", *color).hash_into(values),
+            Background::Gradient { bottom, top } => ("gradient", *bottom, *top).hash_into(values),
+        }
+    }
+}
+
+impl Default for Background {
+    fn default() -> Self {
+        Background::Gradient {
+            bottom: Color::White,
+            top: Color::new(0.5, 0.7, 1.0),
+        }
+    }
+}
+
+/// Collects the contents of a `Scene`. Nothing is indexed until `build`.
+#[derive(Default)]
+pub struct SceneBuilder {
+    objects: Vec<Arc<dyn Hit>>,
+    lights: Vec<Arc<dyn Hit>>,
+    materials: Vec<Arc<dyn Scatter>>,
+    background: Background,
+    camera: Option<Arc<dyn Camera>>,
+}
+
+impl SceneBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add<H: Hit + 'static>(&mut self, object: H) -> &mut Self {
+        self.add_shared(Arc::new(object))
+    }
+
+    pub fn add_shared(&mut self, object: Arc<dyn Hit>) -> &mut Self {
+        self.objects.push(object);
+        self
+    }
+
+    /// Adds an emissive object and also records it as a light.
+    pub fn add_light<H: Hit + 'static>(&mut self, object: H) -> &mut Self {
+        let object: Arc<dyn Hit> = Arc::new(object);
+        self.lights.push(object.clone());
+        self.add_shared(object)
+    }
+
+    /// Registers a material in the scene's table.
+    pub fn add_material(&mut self, material: Arc<dyn Scatter>) -> MaterialId {
+        self.materials.push(material);
+        MaterialId(self.materials.len() - 1)
+    }
+
+    pub fn set_background(&mut self, background: Background) -> &mut Self {
+        self.background = background;
+        self
+    }
+
+    pub fn set_camera<C: Camera + 'static>(&mut self, camera: C) -> &mut Self {
+        self.camera = Some(Arc::new(camera));
+        self
+    }
+
+    pub fn camera(&self) -> Option<&dyn Camera> {
+        self.camera.as_deref()
+    }
+
+    pub fn object_count(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Builds the acceleration structures. Without a camera the scene is
+    /// seen through a square 90 degree pinhole at the origin looking down -z.
+    pub fn build(self) -> Scene {
+        let (bounded, unbounded): (Vec<_>, Vec<_>) = (0..self.objects.len())
+            .map(|i| (i, self.objects[i].bounding_box()))
+            .partition(|(_, bounds)| bounds.is_some());
+        let bounds: Vec<Aabb> = bounded.iter().filter_map(|&(_, bounds)| bounds).collect();
+
+        let camera = self.camera.unwrap_or_else(|| {
+            Arc::new(PerspectiveCamera::new(
+                Point3D::new(0.0, 0.0, 0.0),
+                Point3D::new(0.0, 0.0, -1.0),
+                Vec3D::new(0.0, 1.0, 0.0),
+                FieldOfView::Vertical(90.0),
+                1.0,
+                0.0,
+                1.0,
+            ))
+        });
+
+        Scene {
+            camera,
+            background: self.background,
+            objects: self.objects,
+            lights: self.lights,
+            materials: self.materials,
+            bvh: Bvh::new(&bounds),
+            bounded: bounded.into_iter().map(|(i, _)| i).collect(),
+            unbounded: unbounded.into_iter().map(|(i, _)| i).collect(),
+        }
+    }
+}
+
+/// Everything needed to render an image: the objects with a hierarchy over
+/// them, lights, materials, background and camera.
+pub struct Scene {
+    pub camera: Arc<dyn Camera>,
+    pub background: Background,
+    objects: Vec<Arc<dyn Hit>>,
+    lights: Vec<Arc<dyn Hit>>,
+    materials: Vec<Arc<dyn Scatter>>,
+    bvh: Bvh,
+    // Indices into `objects` for the hierarchy's items and for the objects
+    // without bounds that every ray is tested against
+    bounded: Vec<usize>,
+    unbounded: Vec<usize>,
+}
+
+impl Scene {
+    pub fn builder() -> SceneBuilder {
+        SceneBuilder::new()
+    }
+
+    // Distance to and index of the closest object
+    fn closest(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, usize)> {
+        let mut closest = self.bvh.hit(ray, t_min, t_max, |i, closest_so_far| {
+            let object = self.bounded[i];
+            let t = self.objects[object].intersect(ray, t_min, closest_so_far)?;
+            Some((t, (t, object)))
+        });
+
+        for &object in &self.unbounded {
+            let closest_so_far = closest.map_or(t_max, |(t, _)| t);
+            if let Some(t) = self.objects[object].intersect(ray, t_min, closest_so_far) {
+                closest = Some((t, object));
+            }
+        }
+
+        closest
+    }
+
+    pub fn objects(&self) -> &[Arc<dyn Hit>] {
+        &self.objects
+    }
+
+    pub fn lights(&self) -> &[Arc<dyn Hit>] {
+        &self.lights
+    }
+
+    pub fn material(&self, id: MaterialId) -> &dyn Scatter {
+        self.materials[id.0].as_ref()
+    }
+
+    /// Hash of everything the scene is made of, for telling whether two
+    /// scenes render alike: objects, lights, materials and the textures
+    /// they use, background and camera.
+    pub fn content_hash(&self) -> u64 {
+        let mut values = Vec::new();
+        self.hash_into(&mut values);
+        for light in &self.lights {
+            light.hash_into(&mut values);
+        }
+        self.materials.len().hash_into(&mut values);
+        for material in &self.materials {
+            material.hash_into(&mut values);
+        }
+        self.background.hash_into(&mut values);
+        self.camera.hash_into(&mut values);
+        hash(&values)
+    }
+}
+
+impl Hit for Scene {
+    // Only the closest object fills in a full record
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (_, object) = self.closest(ray, t_min, t_max)?;
+        self.objects[object].hit(ray, t_min, t_max)
+    }
+
+    fn intersect(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<f64> {
+        self.closest(ray, t_min, t_max).map(|(t, _)| t)
+    }
+
+    // Lets a scene of shapes be placed as a group with `Instance`
+    fn bounding_box(&self) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        self.objects
+            .iter()
+            .filter_map(|object| object.bounding_box())
+            .reduce(|a, b| a.union(&b))
+    }
+
+    // Only the objects, as when the scene is instanced as a group
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        self.objects.len().hash_into(values);
+        for object in &self.objects {
+            object.hash_into(values);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        material::{Lambertian, PbrMaterial},
+        mesh::{MeshData, TriangleMesh},
+        object::Sphere,
+        sdf::{SdfNode, SdfObject},
+        texture::ImageTexture,
+    };
+
+    #[test]
+    fn test_content_hash_follows_changes() {
+        let scene = |center: f64, albedo: f64| {
+            let mut builder = Scene::builder();
+            let material = builder.add_material(Arc::new(Lambertian::new(Color::new(
+                albedo, albedo, albedo,
+            ))));
+            builder.add(Sphere::new(Point3D::new(center, 0.0, -3.0), 1.0, material));
+            builder.build().content_hash()
+        };
+        assert_eq!(scene(0.0, 0.5), scene(0.0, 0.5));
+        assert_ne!(scene(0.0, 0.5), scene(0.1, 0.5));
+        assert_ne!(scene(0.0, 0.5), scene(0.0, 0.6));
+    }
+
+    #[test]
+    fn test_content_hash_sees_inside_objects() {
+        // Changes a probe at a few points would miss: one texel, a vertex
+        // inside the bounds, and a distance field
+        let scene = |texel: f64, vertex: f64, radius: f64| {
+            let mut builder = Scene::builder();
+            let mut pixels = vec![Vec3D::new(0.5, 0.5, 0.5); 16];
+            pixels[5] = Vec3D::new(texel, 0.5, 0.5);
+            let texture = Arc::new(ImageTexture::new(4, 4, pixels));
+            let material = builder.add_material(Arc::new(
+                PbrMaterial::new(Color::White, 0.0, 0.5).with_base_color_texture(texture),
+            ));
+            let mesh = MeshData {
+                positions: vec![
+                    Point3D::new(0.0, 0.0, -3.0),
+                    Point3D::new(1.0, 0.0, -3.0),
+                    Point3D::new(0.0, 1.0, -3.0),
+                    Point3D::new(1.0, 1.0, -3.0),
+                    Point3D::new(vertex, vertex, -3.0),
+                ],
+                normals: Vec::new(),
+                uvs: Vec::new(),
+                colors: Vec::new(),
+                indices: vec![[0, 1, 4], [1, 3, 4], [3, 2, 4], [2, 0, 4]],
+            };
+            builder.add(TriangleMesh::new(Arc::new(mesh), material));
+            builder.add(SdfObject::new(Arc::new(SdfNode::sphere(radius)), material));
+            builder.build().content_hash()
+        };
+        assert_eq!(scene(0.5, 0.5, 1.0), scene(0.5, 0.5, 1.0));
+        assert_ne!(scene(0.5, 0.5, 1.0), scene(0.7, 0.5, 1.0));
+        assert_ne!(scene(0.5, 0.5, 1.0), scene(0.5, 0.4, 1.0));
+        assert_ne!(scene(0.5, 0.5, 1.0), scene(0.5, 0.5, 1.1));
+    }
+
+    #[test]
+    fn test_scene_hits_bounded_and_unbounded() {
+        let mut builder = Scene::builder();
+        let material = builder.add_material(Arc::new(Lambertian::new(Color::Black)));
+        for i in 0..20 {
+            let center = Point3D::new(0.0, 0.0, -3.0 - 2.0 * i as f64);
+            builder.add(Sphere::new(center, 0.5, material));
+        }
+        // Distance fields have no bounds, so this one is tested separately
+        builder.add(SdfObject::new(
+            Arc::new(SdfNode::sphere(0.5).translate(Vec3D::new(0.0, 0.0, -2.0))),
+            material,
+        ));
+        builder.set_background(Background::Solid(Color::Red));
+        let scene = builder.build();
+        assert_eq!(scene.objects().len(), 21);
+
+        let ray = Ray::new(Point3D::new(0.0, 0.0, 0.0), Vec3D::new(0.0, 0.0, -1.0), 0.0);
+        let hit = scene.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 1.5).abs() < 1.0e-3);
+        let hit = scene.hit(&ray, 3.6, f64::INFINITY).unwrap();
+        assert!((hit.t - 4.5).abs() < 1.0e-9);
+
+        let up = Vec3D::new(0.0, 1.0, 0.0);
+        assert!(scene
+            .hit(&Ray::new(ray.origin, up, 0.0), 0.001, f64::INFINITY)
+            .is_none());
+        assert_eq!(scene.background.color(up).to_vec3d(), Color::Red.to_vec3d());
+    }
+}
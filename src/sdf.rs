@@ -0,0 +1,399 @@
+use std::sync::Arc;
+
+use crate::{
+    content::HashInto,
+    hit::{Hit, HitRecord},
+    material::MaterialId,
+    ray::Ray,
+    vec::{Point3D, Vec3D},
+};
+
+/// Signed distance to a surface: negative inside, positive outside.
+pub trait Sdf: Send + Sync {
+    fn distance(&self, p: Point3D) -> f64;
+
+    /// Adds what decides the field to `values`, see `Hit::hash_into`.
+    fn hash_into(&self, values: &mut Vec<u64>);
+}
+
+impl<F> Sdf for F
+where
+    F: Fn(Point3D) -> f64 + Send + Sync,
+{
+    fn distance(&self, p: Point3D) -> f64 {
+        self(p)
+    }
+
+    // A closure can't be looked into, so this is the one field hashed by
+    // its values: on a lattice from -8 to 8 on each axis. Use `SdfNode`
+    // where an exact hash matters.
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        "closure".hash_into(values);
+        for i in 0..17 * 17 * 17 {
+            let p = Point3D::new((i % 17) as f64, (i / 17 % 17) as f64, (i / 289) as f64);
+            self(p - Vec3D::new(8.0, 8.0, 8.0)).hash_into(values);
+        }
+    }
+}
+
+/// Composable distance field. Primitives are centered at the origin; tori
+/// and twists are taken around the y axis.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SdfNode {
+    Sphere {
+        radius: f64,
+    },
+    Cuboid {
+        half_extents: Vec3D,
+    },
+    RoundCuboid {
+        half_extents: Vec3D,
+        radius: f64,
+    },
+    Torus {
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    Translate {
+        offset: Vec3D,
+        node: Box<SdfNode>,
+    },
+    Union(Box<SdfNode>, Box<SdfNode>),
+    SmoothUnion {
+        a: Box<SdfNode>,
+        b: Box<SdfNode>,
+        smoothness: f64,
+    },
+    Repeat {
+        period: Vec3D,
+        node: Box<SdfNode>,
+    },
+    Twist {
+        // Radians of rotation around y per unit of height
+        rate: f64,
+        node: Box<SdfNode>,
+    },
+}
+
+impl SdfNode {
+    pub fn sphere(radius: f64) -> Self {
+        SdfNode::Sphere { radius }
+    }
+
+    pub fn cuboid(half_extents: Vec3D) -> Self {
+        SdfNode::Cuboid { half_extents }
+    }
+
+    pub fn round_cuboid(half_extents: Vec3D, radius: f64) -> Self {
+        SdfNode::RoundCuboid {
+            half_extents,
+            radius,
+        }
+    }
+
+    pub fn torus(major_radius: f64, minor_radius: f64) -> Self {
+        SdfNode::Torus {
+            major_radius,
+            minor_radius,
+        }
+    }
+
+    pub fn translate(self, offset: Vec3D) -> Self {
+        SdfNode::Translate {
+            offset,
+            node: Box::new(self),
+        }
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        SdfNode::Union(Box::new(self), Box::new(other))
+    }
+
+    pub fn smooth_union(self, other: Self, smoothness: f64) -> Self {
+        SdfNode::SmoothUnion {
+            a: Box::new(self),
+            b: Box::new(other),
+            smoothness,
+        }
+    }
+
+    /// Tiles space with copies of the node; a zero period leaves that axis alone.
+    pub fn repeat(self, period: Vec3D) -> Self {
+        SdfNode::Repeat {
+            period,
+            node: Box::new(self),
+        }
+    }
+
+    pub fn twist(self, rate: f64) -> Self {
+        SdfNode::Twist {
+            rate,
+            node: Box::new(self),
+        }
+    }
+}
+
+fn max_components(a: Vec3D, b: f64) -> Vec3D {
+    Vec3D::new(a.x.max(b), a.y.max(b), a.z.max(b))
+}
+
+fn cuboid_distance(p: Point3D, half_extents: Vec3D) -> f64 {
+    let q = Vec3D::new(p.x.abs(), p.y.abs(), p.z.abs()) - half_extents;
+    max_components(q, 0.0).length() + q.x.max(q.y.max(q.z)).min(0.0)
+}
+
+fn repeat_axis(x: f64, period: f64) -> f64 {
+    if period == 0.0 {
+        x
+    } else {
+        x - period * (x / period).round()
+    }
+}
+
+impl Sdf for SdfNode {
+    fn distance(&self, p: Point3D) -> f64 {
+        match self {
+            SdfNode::Sphere { radius } => p.length() - radius,
+            SdfNode::Cuboid { half_extents } => cuboid_distance(p, *half_extents),
+            SdfNode::RoundCuboid {
+                half_extents,
+                radius,
+            } => {
+                cuboid_distance(
+                    p,
+                    max_components(*half_extents - Vec3D::new(*radius, *radius, *radius), 0.0),
+                ) - radius
+            }
+            SdfNode::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let ring = (p.x * p.x + p.z * p.z).sqrt() - major_radius;
+                (ring * ring + p.y * p.y).sqrt() - minor_radius
+            }
+            SdfNode::Translate { offset, node } => node.distance(p - *offset),
+            SdfNode::Union(a, b) => a.distance(p).min(b.distance(p)),
+            SdfNode::SmoothUnion { a, b, smoothness } => {
+                // Polynomial smooth minimum
+                let (da, db) = (a.distance(p), b.distance(p));
+                if *smoothness <= 0.0 {
+                    return da.min(db);
+                }
+                let h = (0.5 + 0.5 * (db - da) / smoothness).clamp(0.0, 1.0);
+                db * (1.0 - h) + da * h - smoothness * h * (1.0 - h)
+            }
+            SdfNode::Repeat { period, node } => node.distance(Point3D::new(
+                repeat_axis(p.x, period.x),
+                repeat_axis(p.y, period.y),
+                repeat_axis(p.z, period.z),
+            )),
+            SdfNode::Twist { rate, node } => {
+                let (sin_theta, cos_theta) = (rate * p.y).sin_cos();
+                node.distance(Point3D::new(
+                    cos_theta * p.x - sin_theta * p.z,
+                    p.y,
+                    sin_theta * p.x + cos_theta * p.z,
+                ))
+            }
+        }
+    }
+
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        match self {
+            SdfNode::Sphere { radius } => ("sphere", *radius).hash_into(values),
+            SdfNode::Cuboid { half_extents } => ("cuboid", *half_extents).hash_into(values),
+            SdfNode::RoundCuboid {
+                half_extents,
+                radius,
+            } => ("round cuboid", *half_extents, *radius).hash_into(values),
+            SdfNode::Torus {
+                major_radius,
+                minor_radius,
+            } => ("torus", *major_radius, *minor_radius).hash_into(values),
+            SdfNode::Translate { offset, node } => {
+                ("translate", *offset).hash_into(values);
+                node.hash_into(values);
+            }
+            SdfNode::Union(a, b) => {
+                "union".hash_into(values);
+                a.hash_into(values);
+                b.hash_into(values);
+            }
+            SdfNode::SmoothUnion { a, b, smoothness } => {
+                ("smooth union", *smoothness).hash_into(values);
+                a.hash_into(values);
+                b.hash_into(values);
+            }
+            SdfNode::Repeat { period, node } => {
+                ("repeat", *period).hash_into(values);
+                node.hash_into(values);
+            }
+            SdfNode::Twist { rate, node } => {
+                ("twist", *rate).hash_into(values);
+                node.hash_into(values);
+            }
+        }
+    }
+}
+
+/// Object whose surface is the zero set of a distance field, found by
+/// sphere tracing.
+///
+/// Fields that are not exact distances (twists, smooth unions, most
+/// fractals) can overestimate how far it is safe to step; lowering the step
+/// scale below one trades speed for not tunnelling through thin features.
+#[derive(Clone)]
+pub struct SdfObject {
+    pub sdf: Arc<dyn Sdf>,
+    pub material: MaterialId,
+    pub max_steps: usize,
+    pub epsilon: f64,
+    pub max_distance: f64,
+    pub step_scale: f64,
+}
+
+impl SdfObject {
+    pub fn new(sdf: Arc<dyn Sdf>, material: MaterialId) -> Self {
+        Self {
+            sdf,
+            material,
+            max_steps: 256,
+            epsilon: 1.0e-4,
+            max_distance: 1.0e3,
+            step_scale: 1.0,
+        }
+    }
+
+    pub fn with_step_scale(mut self, step_scale: f64) -> Self {
+        self.step_scale = step_scale;
+        self
+    }
+
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    pub fn with_epsilon(mut self, epsilon: f64) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
+    pub fn with_max_distance(mut self, max_distance: f64) -> Self {
+        self.max_distance = max_distance;
+        self
+    }
+
+    fn normal(&self, p: Point3D) -> Vec3D {
+        // Central differences of the field
+        let h = self.epsilon;
+        let dx = Vec3D::new(h, 0.0, 0.0);
+        let dy = Vec3D::new(0.0, h, 0.0);
+        let dz = Vec3D::new(0.0, 0.0, h);
+        Vec3D::new(
+            self.sdf.distance(p + dx) - self.sdf.distance(p - dx),
+            self.sdf.distance(p + dy) - self.sdf.distance(p - dy),
+            self.sdf.distance(p + dz) - self.sdf.distance(p - dz),
+        )
+        .normalize()
+    }
+}
+
+impl Hit for SdfObject {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        // March in world distance `s` along the unit direction
+        let length = ray.direction.length();
+        let direction = ray.direction / length;
+        let s_max = (t_max * length).min(self.max_distance);
+        let mut s = t_min.max(0.0) * length;
+        let mut steps = 0;
+
+        // A ray leaving the surface it was scattered from starts inside the
+        // epsilon shell; push it out before deciding which side it is on
+        let mut distance = self.sdf.distance(ray.origin + s * direction);
+        while distance.abs() < self.epsilon && steps < self.max_steps {
+            s += self.epsilon;
+            distance = self.sdf.distance(ray.origin + s * direction);
+            steps += 1;
+        }
+        let sign = if distance < 0.0 { -1.0 } else { 1.0 };
+
+        while steps < self.max_steps && s <= s_max {
+            let d = sign * self.sdf.distance(ray.origin + s * direction);
+            if d < self.epsilon {
+                let t = s / length;
+                let hit_point = ray.at(t);
+                let normal = self.normal(hit_point);
+                return Some(HitRecord::new(hit_point, normal, self.material, t, ray));
+            }
+            s += d * self.step_scale;
+            steps += 1;
+        }
+
+        None
+    }
+
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        "sdf".hash_into(values);
+        self.sdf.hash_into(values);
+        (
+            self.material,
+            self.max_steps,
+            self.epsilon,
+            self.max_distance,
+            self.step_scale,
+        )
+            .hash_into(values);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sdf_sphere_matches_analytic() {
+        let material = MaterialId(0);
+        let object = SdfObject::new(
+            Arc::new(SdfNode::sphere(1.0).translate(Vec3D::new(0.0, 0.0, -3.0))),
+            material,
+        );
+
+        let ray = Ray::new(Point3D::new(0.0, 0.0, 0.0), Vec3D::new(0.0, 0.0, -2.0), 0.0);
+        let hit = object.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 1.0).abs() < 1.0e-3);
+        assert!((hit.normal - Vec3D::new(0.0, 0.0, 1.0)).length() < 1.0e-3);
+
+        // Scattered from the surface the ray must not hit the same point again
+        let away = Ray::new(hit.hit_point, Vec3D::new(0.0, 1.0, 1.0), 0.0);
+        assert!(object.hit(&away, 0.001, f64::INFINITY).is_none());
+
+        let miss = Ray::new(Point3D::new(0.0, 2.0, 0.0), Vec3D::new(0.0, 0.0, -1.0), 0.0);
+        assert!(object.hit(&miss, 0.001, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn test_sdf_closure_and_nodes() {
+        let material = MaterialId(0);
+        let plane = |p: Point3D| p.y + 1.0;
+        let object = SdfObject::new(Arc::new(plane), material);
+        let ray = Ray::new(Point3D::new(0.0, 0.0, 0.0), Vec3D::new(0.0, -1.0, 0.0), 0.0);
+        let hit = object.hit(&ray, 0.001, f64::INFINITY).unwrap();
+        assert!((hit.t - 1.0).abs() < 1.0e-3);
+
+        let cuboid = SdfNode::cuboid(Vec3D::new(1.0, 1.0, 1.0));
+        assert!((cuboid.distance(Point3D::new(3.0, 0.0, 0.0)) - 2.0).abs() < 1.0e-12);
+        assert!((cuboid.distance(Point3D::new(0.5, 0.0, 0.0)) + 0.5).abs() < 1.0e-12);
+
+        let repeated = SdfNode::sphere(0.5).repeat(Vec3D::new(4.0, 0.0, 0.0));
+        assert!((repeated.distance(Point3D::new(8.0, 1.0, 0.0)) - 0.5).abs() < 1.0e-12);
+
+        // Smooth union never exceeds the sharp union
+        let a = SdfNode::sphere(1.0);
+        let b = SdfNode::sphere(1.0).translate(Vec3D::new(1.5, 0.0, 0.0));
+        let p = Point3D::new(0.75, 1.0, 0.0);
+        let sharp = a.clone().union(b.clone()).distance(p);
+        let smooth = a.smooth_union(b, 0.5).distance(p);
+        assert!(smooth < sharp);
+    }
+}
@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use crate::{color::Color, content::HashInto, hit::HitRecord, mesh::MeshData, vec::Vec3D};
+
+pub trait Texture: Send + Sync {
+    fn value(&self, hit_record: &HitRecord) -> Color;
+
+    /// Adds what decides the texture's values to `values`, see
+    /// `Hit::hash_into`.
+    fn hash_into(&self, values: &mut Vec<u64>);
+}
+
+impl Texture for Color {
+    fn value(&self, _hit_record: &HitRecord) -> Color {
+        *self
+    }
+
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        ("color", *self).hash_into(values);
+    }
+}
+
+/// Colors stored on the vertices of a mesh, blended across each triangle.
+pub struct VertexColor {
+    mesh: Arc<MeshData>,
+    fallback: Color,
+}
+
+impl VertexColor {
+    pub fn new(mesh: Arc<MeshData>) -> Self {
+        Self {
+            mesh,
+            fallback: Color::White,
+        }
+    }
+
+    /// Color used for hits on other objects, other meshes, or a mesh without
+    /// vertex colors.
+    pub fn with_fallback(mut self, fallback: Color) -> Self {
+        self.fallback = fallback;
+        self
+    }
+}
+
+impl Texture for VertexColor {
+    fn value(&self, hit_record: &HitRecord) -> Color {
+        match &hit_record.triangle {
+            Some(triangle)
+                if Arc::ptr_eq(&triangle.mesh, &self.mesh) && !self.mesh.colors.is_empty() =>
+            {
+                let [i0, i1, i2] = self.mesh.indices[triangle.index];
+                let b0 = 1.0 - triangle.b1 - triangle.b2;
+                Color::RGB(
+                    b0 * self.mesh.colors[i0].to_vec3d()
+                        + triangle.b1 * self.mesh.colors[i1].to_vec3d()
+                        + triangle.b2 * self.mesh.colors[i2].to_vec3d(),
+                )
+            }
+            _ => self.fallback,
+        }
+    }
+
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        ("vertex color", &self.mesh, self.fallback).hash_into(values);
+    }
+}
+
+/// Decodes an sRGB-encoded channel in `[0, 1]` into linear light.
+pub fn srgb_to_linear(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Bitmap in linear RGB, sampled with bilinear filtering and repeating at
+/// the edges. Rows are stored top to bottom while `v` runs from the bottom
+/// of the image up.
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Vec3D>,
+}
+
+impl ImageTexture {
+    pub fn new(width: usize, height: usize, pixels: Vec<Vec3D>) -> Self {
+        assert_eq!(
+            pixels.len(),
+            width * height,
+            "pixel count must match the size"
+        );
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    fn texel(&self, x: isize, y: isize) -> Vec3D {
+        let x = x.rem_euclid(self.width as isize) as usize;
+        let y = y.rem_euclid(self.height as isize) as usize;
+        self.pixels[y * self.width + x]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, hit_record: &HitRecord) -> Color {
+        if self.pixels.is_empty() {
+            return Color::Black;
+        }
+
+        let x = hit_record.u * self.width as f64 - 0.5;
+        let y = (1.0 - hit_record.v) * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as isize, y0 as isize);
+
+        let top = (1.0 - fx) * self.texel(x0, y0) + fx * self.texel(x0 + 1, y0);
+        let bottom = (1.0 - fx) * self.texel(x0, y0 + 1) + fx * self.texel(x0 + 1, y0 + 1);
+        Color::RGB((1.0 - fy) * top + fy * bottom)
+    }
+
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        ("image", self.width, self.height, &self.pixels).hash_into(values);
+    }
+}
@@ -0,0 +1,119 @@
+/// Rectangle of pixels rendered as one unit of work, from `(x0, y0)` up to
+/// but not including `(x1, y1)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tile {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl Tile {
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (self.y0..self.y1).flat_map(move |y| (self.x0..self.x1).map(move |x| (x, y)))
+    }
+
+    pub fn pixel_count(&self) -> usize {
+        (self.x1 - self.x0) * (self.y1 - self.y0)
+    }
+}
+
+/// Order tiles are handed out in. Neighbouring tiles touch the same parts
+/// of the scene, so keeping them close in time helps caches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TileOrder {
+    /// Row by row from the bottom.
+    Scanline,
+    /// Along a Hilbert curve, which never jumps between distant tiles.
+    #[default]
+    Hilbert,
+    /// Outwards from the center, where the subject usually is.
+    Spiral,
+}
+
+/// Splits an image into tiles of at most `size` pixels square.
+pub fn tiles(width: usize, height: usize, size: usize, order: TileOrder) -> Vec<Tile> {
+    let size = size.max(1);
+    let (columns, rows) = (width.div_ceil(size), height.div_ceil(size));
+    let mut cells: Vec<(usize, usize)> = (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .collect();
+
+    match order {
+        TileOrder::Scanline => {}
+        TileOrder::Hilbert => {
+            let side = columns.max(rows).next_power_of_two();
+            cells.sort_by_key(|&(column, row)| hilbert_index(side, column, row));
+        }
+        TileOrder::Spiral => {
+            // Ring by ring, each walked counterclockwise from its right side
+            let center = ((columns as f64 - 1.0) / 2.0, (rows as f64 - 1.0) / 2.0);
+            let key = |&(column, row): &(usize, usize)| {
+                let (dx, dy) = (column as f64 - center.0, row as f64 - center.1);
+                let ring = dx.abs().max(dy.abs());
+                let angle = dy.atan2(dx).rem_euclid(std::f64::consts::TAU);
+                (ring, angle)
+            };
+            cells.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap());
+        }
+    }
+
+    cells
+        .into_iter()
+        .map(|(column, row)| Tile {
+            x0: column * size,
+            y0: row * size,
+            x1: ((column + 1) * size).min(width),
+            y1: ((row + 1) * size).min(height),
+        })
+        .collect()
+}
+
+// Distance along the Hilbert curve filling a `side` by `side` grid, `side`
+// being a power of two
+fn hilbert_index(side: usize, mut x: usize, mut y: usize) -> usize {
+    let mut index = 0;
+    let mut s = side / 2;
+    while s > 0 {
+        let rx = usize::from(x & s > 0);
+        let ry = usize::from(y & s > 0);
+        index += s * s * ((3 * rx) ^ ry);
+        // Rotate the quadrant so the curve stays continuous
+        if ry == 0 {
+            if rx == 1 {
+                x = side - 1 - x;
+                y = side - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tiles_cover_image_once() {
+        for order in [TileOrder::Scanline, TileOrder::Hilbert, TileOrder::Spiral] {
+            let tiles = tiles(100, 70, 32, order);
+            assert_eq!(tiles.len(), 4 * 3);
+            let mut covered = vec![0; 100 * 70];
+            for tile in &tiles {
+                for (x, y) in tile.pixels() {
+                    covered[y * 100 + x] += 1;
+                }
+            }
+            assert!(covered.iter().all(|&count| count == 1));
+        }
+
+        // Consecutive tiles along the Hilbert curve are always neighbours
+        let tiles = tiles(256, 256, 32, TileOrder::Hilbert);
+        for pair in tiles.windows(2) {
+            let distance = pair[0].x0.abs_diff(pair[1].x0) + pair[0].y0.abs_diff(pair[1].y0);
+            assert_eq!(distance, 32);
+        }
+    }
+}
@@ -1,8 +1,8 @@
-use std::ops::{Add, Div, Mul, Range, Sub};
+use std::ops::{Add, Div, Index, Mul, Range, Sub};
 
 use rand::Rng;
 
-use crate::color::Color;
+use crate::content::HashInto;
 
 pub type Point3D = Vec3D;
 
@@ -65,9 +65,7 @@ impl Vec3D {
         }
     }
 
-    pub fn random(range: Range<f64>) -> Self {
-        let mut rng = rand::thread_rng();
-
+    pub fn random<R: Rng + ?Sized>(range: Range<f64>, rng: &mut R) -> Self {
         Self {
             x: rng.gen_range(range.clone()),
             y: rng.gen_range(range.clone()),
@@ -75,16 +73,22 @@ impl Vec3D {
         }
     }
 
-    pub fn random_in_unit_sphere() -> Self {
-        let mut v = Self::random(-1.0..1.0);
-        while v.length() >= 1.0 {
-            v = Self::random(-1.0..1.0);
-        }
-        v
+    /// Maps a point of the unit square onto the unit sphere, keeping
+    /// the area of every region.
+    pub fn on_unit_sphere((u, v): (f64, f64)) -> Self {
+        let z = 1.0 - 2.0 * u;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * std::f64::consts::PI * v;
+        Self::new(r * phi.cos(), r * phi.sin(), z)
+    }
+
+    /// Uniform point inside the unit sphere, `radius` picking how deep.
+    pub fn in_unit_sphere(direction: (f64, f64), radius: f64) -> Self {
+        radius.cbrt() * Self::on_unit_sphere(direction)
     }
 
-    pub fn random_in_hemisphere(normal: Vec3D) -> Self {
-        let in_unit_sphere = Self::random_in_unit_sphere();
+    pub fn in_hemisphere(normal: Vec3D, direction: (f64, f64), radius: f64) -> Self {
+        let in_unit_sphere = Self::in_unit_sphere(direction, radius);
 
         if in_unit_sphere.dot(normal) > 0.0 {
             in_unit_sphere
@@ -93,20 +97,20 @@ impl Vec3D {
         }
     }
 
-    pub fn random_in_unit_disk() -> Self {
-        let mut rng = rand::thread_rng();
-
-        /* loop {
-            let p = Vec3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
-            if p.length() < 1.0 {
-                return p;
-            }
-        } */
-        let mut p = Vec3D::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
-        while p.length() >= 1.0 {
-            p = Vec3D::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
+    /// Concentric mapping of the unit square onto the unit disk in the xy
+    /// plane, which keeps strata of the square together.
+    pub fn in_unit_disk((u, v): (f64, f64)) -> Self {
+        let (a, b) = (2.0 * u - 1.0, 2.0 * v - 1.0);
+        if a == 0.0 && b == 0.0 {
+            return Self::new(0.0, 0.0, 0.0);
         }
-        p
+        let quarter = std::f64::consts::FRAC_PI_4;
+        let (r, theta) = if a.abs() > b.abs() {
+            (a, quarter * (b / a))
+        } else {
+            (b, 2.0 * quarter - quarter * (a / b))
+        };
+        Self::new(r * theta.cos(), r * theta.sin(), 0.0)
     }
 
     pub fn is_near_zero(self) -> bool {
@@ -126,6 +130,453 @@ impl Vec3D {
     }
 }
 
+/// Orthonormal basis whose `w` axis points along a given direction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Basis {
+    pub u: Vec3D,
+    pub v: Vec3D,
+    pub w: Vec3D,
+}
+
+impl Basis {
+    pub fn from_w(direction: Vec3D) -> Self {
+        // Branchless construction from Duff et al., "Building an Orthonormal Basis, Revisited"
+        let w = direction.normalize();
+        let sign = 1.0f64.copysign(w.z);
+        let a = -1.0 / (sign + w.z);
+        let b = w.x * w.y * a;
+        let u = Vec3D::new(1.0 + sign * w.x * w.x * a, sign * b, -sign * w.x);
+        let v = Vec3D::new(b, sign + w.y * w.y * a, -w.y);
+
+        Self { u, v, w }
+    }
+
+    pub fn to_local(&self, a: Vec3D) -> Vec3D {
+        Vec3D::new(a.dot(self.u), a.dot(self.v), a.dot(self.w))
+    }
+
+    pub fn to_world(&self, a: Vec3D) -> Vec3D {
+        a.x * self.u + a.y * self.v + a.z * self.w
+    }
+}
+
+/// Row-major 4x4 matrix acting on homogeneous coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix4 {
+    pub m: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+    pub fn new(m: [[f64; 4]; 4]) -> Self {
+        Self { m }
+    }
+
+    pub fn identity() -> Self {
+        Self::new([
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Self::new(m)
+    }
+
+    pub fn inverse(&self) -> Option<Self> {
+        // Gauss-Jordan elimination with partial pivoting
+        let mut a = self.m;
+        let mut inv = Self::identity().m;
+
+        for col in 0..4 {
+            let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+            if a[pivot][col] == 0.0 {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+
+            for row in 0..4 {
+                if row == col {
+                    continue;
+                }
+                let factor = a[row][col];
+                for j in 0..4 {
+                    a[row][j] -= factor * a[col][j];
+                    inv[row][j] -= factor * inv[col][j];
+                }
+            }
+        }
+
+        Some(Self::new(inv))
+    }
+
+    pub fn transform_point(&self, p: Point3D) -> Point3D {
+        let m = &self.m;
+        let x = m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3];
+        let y = m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3];
+        let z = m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3];
+        let w = m[3][0] * p.x + m[3][1] * p.y + m[3][2] * p.z + m[3][3];
+        if w == 1.0 {
+            Vec3D::new(x, y, z)
+        } else {
+            Vec3D::new(x, y, z) / w
+        }
+    }
+
+    pub fn transform_vector(&self, v: Vec3D) -> Vec3D {
+        let m = &self.m;
+        Vec3D::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Self::new(m)
+    }
+}
+
+/// Affine transform that keeps its inverse alongside, so rays can be moved
+/// into object space and normals back out without inverting per hit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub matrix: Matrix4,
+    pub inverse: Matrix4,
+}
+
+impl Transform {
+    pub fn new(matrix: Matrix4) -> Option<Self> {
+        Some(Self {
+            matrix,
+            inverse: matrix.inverse()?,
+        })
+    }
+
+    pub fn identity() -> Self {
+        Self {
+            matrix: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+        }
+    }
+
+    pub fn translate(delta: Vec3D) -> Self {
+        let matrix = Matrix4::new([
+            [1.0, 0.0, 0.0, delta.x],
+            [0.0, 1.0, 0.0, delta.y],
+            [0.0, 0.0, 1.0, delta.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let inverse = Matrix4::new([
+            [1.0, 0.0, 0.0, -delta.x],
+            [0.0, 1.0, 0.0, -delta.y],
+            [0.0, 0.0, 1.0, -delta.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        Self { matrix, inverse }
+    }
+
+    pub fn scale(factor: Vec3D) -> Self {
+        let matrix = Matrix4::new([
+            [factor.x, 0.0, 0.0, 0.0],
+            [0.0, factor.y, 0.0, 0.0],
+            [0.0, 0.0, factor.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let inverse = Matrix4::new([
+            [1.0 / factor.x, 0.0, 0.0, 0.0],
+            [0.0, 1.0 / factor.y, 0.0, 0.0],
+            [0.0, 0.0, 1.0 / factor.z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        Self { matrix, inverse }
+    }
+
+    /// Rotation by `degrees` counter-clockwise around `axis`.
+    pub fn rotate(axis: Vec3D, degrees: f64) -> Self {
+        let a = axis.normalize();
+        let (sin_theta, cos_theta) = degrees.to_radians().sin_cos();
+        let k = 1.0 - cos_theta;
+
+        let matrix = Matrix4::new([
+            [
+                a.x * a.x * k + cos_theta,
+                a.x * a.y * k - a.z * sin_theta,
+                a.x * a.z * k + a.y * sin_theta,
+                0.0,
+            ],
+            [
+                a.x * a.y * k + a.z * sin_theta,
+                a.y * a.y * k + cos_theta,
+                a.y * a.z * k - a.x * sin_theta,
+                0.0,
+            ],
+            [
+                a.x * a.z * k - a.y * sin_theta,
+                a.y * a.z * k + a.x * sin_theta,
+                a.z * a.z * k + cos_theta,
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        // Rotations are orthogonal
+        Self {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+
+    pub fn inverse(&self) -> Self {
+        Self {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn point(&self, p: Point3D) -> Point3D {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn vector(&self, v: Vec3D) -> Vec3D {
+        self.matrix.transform_vector(v)
+    }
+
+    pub fn normal(&self, n: Vec3D) -> Vec3D {
+        // Normals transform by the inverse transpose to stay perpendicular
+        self.inverse.transpose().transform_vector(n)
+    }
+}
+
+/// `a * b` applies `b` first, then `a`.
+impl Mul for Transform {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self {
+            matrix: self.matrix * other.matrix,
+            inverse: other.inverse * self.inverse,
+        }
+    }
+}
+
+/// Unit quaternion representing a rotation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quaternion {
+    pub fn identity() -> Self {
+        Self {
+            w: 1.0,
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+        }
+    }
+
+    /// Rotation by `degrees` counter-clockwise around `axis`.
+    pub fn from_axis_angle(axis: Vec3D, degrees: f64) -> Self {
+        let axis = axis.normalize();
+        let (sin_half, cos_half) = (degrees.to_radians() / 2.0).sin_cos();
+        Self {
+            w: cos_half,
+            x: axis.x * sin_half,
+            y: axis.y * sin_half,
+            z: axis.z * sin_half,
+        }
+    }
+
+    pub fn dot(&self, other: Self) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn normalize(self) -> Self {
+        let length = self.dot(self).sqrt();
+        Self {
+            w: self.w / length,
+            x: self.x / length,
+            y: self.y / length,
+            z: self.z / length,
+        }
+    }
+
+    /// Spherical linear interpolation along the shortest arc.
+    pub fn slerp(self, other: Self, t: f64) -> Self {
+        let mut cos_theta = self.dot(other);
+        let other = if cos_theta < 0.0 {
+            cos_theta = -cos_theta;
+            Self {
+                w: -other.w,
+                x: -other.x,
+                y: -other.y,
+                z: -other.z,
+            }
+        } else {
+            other
+        };
+
+        let (a, b) = if cos_theta > 0.9995 {
+            // Nearly parallel: fall back to normalized linear interpolation
+            (1.0 - t, t)
+        } else {
+            let theta = cos_theta.acos();
+            let sin_theta = theta.sin();
+            (
+                ((1.0 - t) * theta).sin() / sin_theta,
+                (t * theta).sin() / sin_theta,
+            )
+        };
+
+        Self {
+            w: a * self.w + b * other.w,
+            x: a * self.x + b * other.x,
+            y: a * self.y + b * other.y,
+            z: a * self.z + b * other.z,
+        }
+        .normalize()
+    }
+
+    pub fn to_matrix(self) -> Matrix4 {
+        let Self { w, x, y, z } = self;
+        Matrix4::new([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+}
+
+/// Translation, rotation and scale of an object at a point in time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Keyframe {
+    pub time: f64,
+    pub translation: Vec3D,
+    pub rotation: Quaternion,
+    pub scale: Vec3D,
+}
+
+impl Keyframe {
+    pub fn new(time: f64, translation: Vec3D, rotation: Quaternion, scale: Vec3D) -> Self {
+        Self {
+            time,
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn to_transform(&self) -> Transform {
+        let rotation = self.rotation.to_matrix();
+        Transform::translate(self.translation)
+            * Transform {
+                matrix: rotation,
+                inverse: rotation.transpose(),
+            }
+            * Transform::scale(self.scale)
+    }
+
+    fn lerp(&self, other: &Self, time: f64) -> Self {
+        let t = (time - self.time) / (other.time - self.time);
+        Self {
+            time,
+            translation: (1.0 - t) * self.translation + t * other.translation,
+            rotation: self.rotation.slerp(other.rotation, t),
+            scale: (1.0 - t) * self.scale + t * other.scale,
+        }
+    }
+}
+
+/// Transform interpolated between keyframes sorted by time. Rotations are
+/// interpolated with slerp rather than per matrix element, so a spinning
+/// object keeps its shape while it blurs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimatedTransform {
+    keyframes: Vec<Keyframe>,
+}
+
+impl AnimatedTransform {
+    pub fn new(mut keyframes: Vec<Keyframe>) -> Self {
+        assert!(
+            !keyframes.is_empty(),
+            "an animation needs at least one keyframe"
+        );
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self { keyframes }
+    }
+
+    pub fn at(&self, time: f64) -> Transform {
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.time <= time);
+        if next == 0 {
+            return self.keyframes[0].to_transform();
+        }
+        if next == self.keyframes.len() {
+            return self.keyframes[next - 1].to_transform();
+        }
+        self.keyframes[next - 1]
+            .lerp(&self.keyframes[next], time)
+            .to_transform()
+    }
+}
+
+impl Index<usize> for Vec3D {
+    type Output = f64;
+
+    fn index(&self, axis: usize) -> &f64 {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("axis {} out of range for Vec3D", axis),
+        }
+    }
+}
+
 impl Add for Vec3D {
     type Output = Self;
 
@@ -138,10 +589,10 @@ impl Add for Vec3D {
     }
 }
 
-impl<'this, 'other> Add<&'other Vec3D> for &'this Vec3D {
+impl Add<&Vec3D> for &Vec3D {
     type Output = Vec3D;
 
-    fn add(self, other: &'other Vec3D) -> Vec3D {
+    fn add(self, other: &Vec3D) -> Vec3D {
         Vec3D {
             x: self.x + other.x,
             y: self.y + other.y,
@@ -162,10 +613,10 @@ impl Sub for Vec3D {
     }
 }
 
-impl<'this, 'other> Sub<&'other Vec3D> for &'this Vec3D {
+impl Sub<&Vec3D> for &Vec3D {
     type Output = Vec3D;
 
-    fn sub(self, other: &'other Vec3D) -> Vec3D {
+    fn sub(self, other: &Vec3D) -> Vec3D {
         Vec3D {
             x: self.x - other.x,
             y: self.y - other.y,
@@ -186,10 +637,10 @@ impl Mul for Vec3D {
     }
 }
 
-impl<'this, 'other> Mul<&'other Vec3D> for &'this Vec3D {
+impl Mul<&Vec3D> for &Vec3D {
     type Output = Vec3D;
 
-    fn mul(self, other: &'other Vec3D) -> Vec3D {
+    fn mul(self, other: &Vec3D) -> Vec3D {
         Vec3D {
             x: self.x * other.x,
             y: self.y * other.y,
@@ -210,10 +661,10 @@ impl Mul<Vec3D> for f64 {
     }
 }
 
-impl<'other> Mul<&'other Vec3D> for f64 {
+impl Mul<&Vec3D> for f64 {
     type Output = Vec3D;
 
-    fn mul(self, other: &'other Vec3D) -> Vec3D {
+    fn mul(self, other: &Vec3D) -> Vec3D {
         Vec3D {
             x: self * other.x,
             y: self * other.y,
@@ -234,7 +685,7 @@ impl Mul<f64> for Vec3D {
     }
 }
 
-impl<'this> Mul<f64> for &'this Vec3D {
+impl Mul<f64> for &Vec3D {
     type Output = Vec3D;
 
     fn mul(self, scalar: f64) -> Vec3D {
@@ -258,7 +709,7 @@ impl Div<f64> for Vec3D {
     }
 }
 
-impl<'this> Div<f64> for &'this Vec3D {
+impl Div<f64> for &Vec3D {
     type Output = Vec3D;
 
     fn div(self, scalar: f64) -> Vec3D {
@@ -270,11 +721,49 @@ impl<'this> Div<f64> for &'this Vec3D {
     }
 }
 
+impl HashInto for Vec3D {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        (self.x, self.y, self.z).hash_into(values);
+    }
+}
+
+impl HashInto for Basis {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        (self.u, self.v, self.w).hash_into(values);
+    }
+}
+
+impl HashInto for Transform {
+    // The inverse follows from the matrix
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        self.matrix.m.hash_into(values);
+    }
+}
+
+impl HashInto for Quaternion {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        (self.w, self.x, self.y, self.z).hash_into(values);
+    }
+}
+
+impl HashInto for Keyframe {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        (self.time, self.translation, self.rotation, self.scale).hash_into(values);
+    }
+}
+
+impl HashInto for AnimatedTransform {
+    fn hash_into(&self, values: &mut Vec<u64>) {
+        self.keyframes.hash_into(values);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::op_ref)]
     fn test_point_3d_dot_product_borrow() {
         let a = Vec3D {
             x: 1.0,
@@ -385,4 +874,88 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_basis_is_orthonormal() {
+        for direction in [
+            Vec3D::new(0.0, 0.0, 1.0),
+            Vec3D::new(0.0, 0.0, -1.0),
+            Vec3D::new(1.0, 2.0, -3.0),
+        ] {
+            let basis = Basis::from_w(direction);
+            assert!(basis.u.dot(basis.v).abs() < 1.0e-12);
+            assert!(basis.u.dot(basis.w).abs() < 1.0e-12);
+            assert!(basis.v.dot(basis.w).abs() < 1.0e-12);
+            assert!((basis.u.cross(basis.v) - basis.w).is_near_zero());
+
+            let a = Vec3D::new(0.3, -1.2, 4.0);
+            assert!((basis.to_world(basis.to_local(a)) - a).length() < 1.0e-12);
+        }
+    }
+
+    #[test]
+    fn test_transform_inverse() {
+        let transform = Transform::translate(Vec3D::new(1.0, -2.0, 3.0))
+            * Transform::rotate(Vec3D::new(1.0, 1.0, 0.0), 30.0)
+            * Transform::scale(Vec3D::new(2.0, 0.5, 4.0));
+
+        let inverse = transform.matrix.inverse().unwrap();
+        let product = transform.matrix * transform.inverse;
+        for i in 0..4 {
+            for j in 0..4 {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((product.m[i][j] - expected).abs() < 1.0e-12);
+                assert!((inverse.m[i][j] - transform.inverse.m[i][j]).abs() < 1.0e-12);
+            }
+        }
+
+        let p = Point3D::new(0.5, 0.25, -1.0);
+        assert!((transform.inverse().point(transform.point(p)) - p).length() < 1.0e-12);
+    }
+
+    #[test]
+    fn test_transform_normal_stays_perpendicular() {
+        let transform = Transform::scale(Vec3D::new(1.0, 4.0, 1.0));
+        let tangent = Vec3D::new(1.0, 1.0, 0.0);
+        let normal = Vec3D::new(1.0, -1.0, 0.0);
+
+        assert!(
+            transform
+                .vector(tangent)
+                .dot(transform.normal(normal))
+                .abs()
+                < 1.0e-12
+        );
+        assert_eq!(
+            transform.point(Point3D::new(1.0, 1.0, 1.0)),
+            Point3D::new(1.0, 4.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn test_animated_transform_interpolates_rotation() {
+        let animation = AnimatedTransform::new(vec![
+            Keyframe::new(
+                1.0,
+                Vec3D::new(2.0, 0.0, 0.0),
+                Quaternion::from_axis_angle(Vec3D::new(0.0, 0.0, 1.0), 180.0),
+                Vec3D::new(1.0, 1.0, 1.0),
+            ),
+            Keyframe::new(
+                0.0,
+                Vec3D::new(0.0, 0.0, 0.0),
+                Quaternion::identity(),
+                Vec3D::new(1.0, 1.0, 1.0),
+            ),
+        ]);
+
+        let p = Point3D::new(1.0, 0.0, 0.0);
+        let halfway = animation.at(0.5).point(p);
+        assert!((halfway - Point3D::new(1.0, 1.0, 0.0)).length() < 1.0e-12);
+
+        // Clamped outside the keyframe range
+        let end = animation.at(2.0).point(p);
+        assert!((end - Point3D::new(1.0, 0.0, 0.0)).length() < 1.0e-12);
+        assert_eq!(animation.at(-1.0).point(p), p);
+    }
 }